@@ -0,0 +1,317 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::data::load_ron;
+
+const DEFAULT_BINDINGS_PATH: &str = "data/bindings.ron";
+/// Rebound controls live under the platform config dir, e.g. `~/.config/chat_rpg` on Linux.
+const USER_BINDINGS_PATH: &str = "chat_rpg/bindings.ron";
+/// How far a stick has to be pushed to count as pressed.
+const STICK_THRESHOLD: f32 = 0.5;
+
+#[derive(Default)]
+pub struct ActionsPlugin {
+  /// Used as they are when set, without touching any files, e.g. in tests. Otherwise the
+  /// defaults and the player's rebindings are read on startup.
+  pub bindings: Option<ActionBindings>
+}
+
+/// Things the player can do, independent of the key or button bound to them. Systems read
+/// these from `Res<Input<Action>>` rather than from the keyboard.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+  MoveUp,
+  MoveDown,
+  MoveLeft,
+  MoveRight,
+  Confirm,
+  Cancel,
+  Inventory,
+  Equip,
+  Controls,
+  SaveGame,
+  LoadGame,
+  NextSaveSlot,
+  /// Ends a fight on the spot; only does anything with the `debug` feature.
+  LeaveCombat
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StickDirection {
+  Positive,
+  Negative
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+  Key(KeyCode),
+  Button(GamepadButtonType),
+  Stick(GamepadAxisType, StickDirection)
+}
+
+impl Binding {
+    pub fn label(&self) -> String {
+      match self {
+        Binding::Key(key) => format!("{:?}", key),
+        Binding::Button(button) => format!("Pad {:?}", button),
+        Binding::Stick(axis, StickDirection::Positive) => format!("{:?}+", axis),
+        Binding::Stick(axis, StickDirection::Negative) => format!("{:?}-", axis),
+      }
+    }
+
+    fn same_device(&self, other: &Binding) -> bool {
+      matches!(
+        (self, other),
+        (Binding::Key(_), Binding::Key(_)) | (Binding::Button(_) | Binding::Stick(..), Binding::Button(_) | Binding::Stick(..))
+      )
+    }
+}
+
+/// What is bound to each action. The defaults come from `assets/data/bindings.ron`; any
+/// rebinding is saved separately and layered over them.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ActionBindings(pub BTreeMap<Action, Vec<Binding>>);
+
+impl ActionBindings {
+    pub fn defaults() -> Self {
+      load_ron(DEFAULT_BINDINGS_PATH)
+    }
+
+    /// The defaults with the player's saved rebindings on top. A damaged file is ignored.
+    pub fn load() -> Self {
+      let mut bindings = Self::defaults();
+      let Some(path) = user_bindings_path().filter(|path| path.exists()) else {
+        return bindings;
+      };
+      let user = fs::read_to_string(&path)
+        .map_err(|error| error.to_string())
+        .and_then(|source| ron::from_str::<ActionBindings>(&source).map_err(|error| error.to_string()));
+      match user {
+        Ok(user) => bindings.0.extend(user.0),
+        Err(error) => warn!("Ignoring controls in {}: {}", path.display(), error),
+      }
+      bindings
+    }
+
+    pub fn save(&self) {
+      let Some(path) = user_bindings_path() else {
+        warn!("No config directory to save controls to");
+        return;
+      };
+      let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .map_err(|error| error.to_string())
+        .and_then(|_| {
+          ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(|error| error.to_string())
+        })
+        .and_then(|source| fs::write(&path, source).map_err(|error| error.to_string()));
+      if let Err(error) = result {
+        warn!("Could not save controls to {}: {}", path.display(), error);
+      }
+    }
+
+    /// Binds `binding` to `action`, replacing whatever it had from the same device so a
+    /// keyboard rebind leaves the gamepad alone and the other way round.
+    ///
+    /// If another action already uses `binding`, the two swap: that action gets `action`'s
+    /// old binding from the same device and is returned. When `action` has nothing to hand
+    /// over, nothing changes and the other action comes back as the error.
+    pub fn rebind(&mut self, action: Action, binding: Binding) -> Result<Option<Action>, Action> {
+      let other = self
+        .0
+        .iter()
+        .find(|(&other, bindings)| other != action && bindings.contains(&binding))
+        .map(|(&other, _)| other);
+      if let Some(other) = other {
+        let old = self
+          .0
+          .get(&action)
+          .and_then(|bindings| bindings.iter().find(|existing| existing.same_device(&binding)))
+          .copied()
+          .ok_or(other)?;
+        for existing in self.0.entry(other).or_default() {
+          if *existing == binding {
+            *existing = old;
+          }
+        }
+      }
+
+      let bindings = self.0.entry(action).or_default();
+      bindings.retain(|existing| !existing.same_device(&binding));
+      bindings.push(binding);
+      Ok(other)
+    }
+}
+
+fn user_bindings_path() -> Option<PathBuf> {
+  Some(dirs::config_dir()?.join(USER_BINDINGS_PATH))
+}
+
+/// Actions whose bindings were held last frame, to find the frame they go down or up.
+#[derive(Resource, Default)]
+struct HeldActions(HashSet<Action>);
+
+/// Actions held down from code instead of a device, e.g. by tests running without a window.
+/// They go through `update_actions` like a held key, so `just_pressed` and `just_released`
+/// behave the same way.
+#[derive(Resource, Default)]
+pub struct InjectedActions(pub HashSet<Action>);
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(self.bindings.clone().unwrap_or_else(ActionBindings::load))
+        .init_resource::<Input<Action>>()
+        .init_resource::<HeldActions>()
+        .init_resource::<InjectedActions>()
+        .add_system_to_stage(CoreStage::PreUpdate, update_actions.after(InputSystem));
+    }
+}
+
+/// Presses an action when one of its bindings or an injected press goes down and releases it
+/// when the last one comes up.
+#[allow(clippy::too_many_arguments)]
+fn update_actions(
+  bindings: Res<ActionBindings>,
+  keyboard: Res<Input<KeyCode>>,
+  gamepads: Res<Gamepads>,
+  buttons: Res<Input<GamepadButton>>,
+  axes: Res<Axis<GamepadAxis>>,
+  injected: Res<InjectedActions>,
+  mut held: ResMut<HeldActions>,
+  mut actions: ResMut<Input<Action>>
+) {
+  actions.clear();
+
+  let binding_held = |binding: &Binding| match *binding {
+    Binding::Key(key) => keyboard.pressed(key),
+    Binding::Button(button_type) => gamepads
+      .iter()
+      .any(|gamepad| buttons.pressed(GamepadButton::new(gamepad, button_type))),
+    Binding::Stick(axis_type, direction) => gamepads.iter().any(|gamepad| {
+      let value = axes.get(GamepadAxis::new(gamepad, axis_type)).unwrap_or(0.0);
+      match direction {
+        StickDirection::Positive => value >= STICK_THRESHOLD,
+        StickDirection::Negative => value <= -STICK_THRESHOLD,
+      }
+    }),
+  };
+
+  let all_actions: BTreeSet<Action> = bindings
+    .0
+    .keys()
+    .chain(&injected.0)
+    .chain(&held.0)
+    .copied()
+    .collect();
+  for action in all_actions {
+    let bound = bindings.0.get(&action).is_some_and(|action_bindings| action_bindings.iter().any(binding_held));
+    let now = bound || injected.0.contains(&action);
+    let before = held.0.contains(&action);
+    if now && !before {
+      held.0.insert(action);
+      actions.press(action);
+    } else if !now && before {
+      held.0.remove(&action);
+      actions.release(action);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::InputPlugin;
+
+    use super::*;
+
+    fn actions_app() -> App {
+      let mut app = App::new();
+      app
+        .add_plugins(MinimalPlugins)
+        .add_plugin(InputPlugin)
+        .add_plugin(ActionsPlugin { bindings: Some(ActionBindings::default()) });
+      app
+    }
+
+    fn bindings(entries: &[(Action, &[Binding])]) -> ActionBindings {
+      ActionBindings(entries.iter().map(|(action, bindings)| (*action, bindings.to_vec())).collect())
+    }
+
+    #[test]
+    fn injected_actions_press_and_release() {
+      let mut app = actions_app();
+
+      app.world.resource_mut::<InjectedActions>().0.insert(Action::Confirm);
+      app.update();
+      assert!(app.world.resource::<Input<Action>>().just_pressed(Action::Confirm));
+
+      app.update();
+      let actions = app.world.resource::<Input<Action>>();
+      assert!(actions.pressed(Action::Confirm));
+      assert!(!actions.just_pressed(Action::Confirm));
+
+      app.world.resource_mut::<InjectedActions>().0.remove(&Action::Confirm);
+      app.update();
+      let actions = app.world.resource::<Input<Action>>();
+      assert!(actions.just_released(Action::Confirm));
+      assert!(!actions.pressed(Action::Confirm));
+    }
+
+    #[test]
+    fn injected_and_bound_presses_hold_together() {
+      let mut app = actions_app();
+      app.world.resource_mut::<ActionBindings>().rebind(Action::Confirm, Binding::Key(KeyCode::Space)).unwrap();
+
+      app.world.resource_mut::<InjectedActions>().0.insert(Action::Confirm);
+      app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::Space);
+      app.update();
+      app.world.resource_mut::<InjectedActions>().0.remove(&Action::Confirm);
+      app.update();
+      assert!(app.world.resource::<Input<Action>>().pressed(Action::Confirm));
+
+      app.world.resource_mut::<Input<KeyCode>>().release(KeyCode::Space);
+      app.update();
+      assert!(app.world.resource::<Input<Action>>().just_released(Action::Confirm));
+    }
+
+    #[test]
+    fn rebind_replaces_only_the_same_device() {
+      let mut bindings = bindings(&[(Action::Confirm, &[Binding::Key(KeyCode::Space), Binding::Button(GamepadButtonType::South)])]);
+
+      assert_eq!(bindings.rebind(Action::Confirm, Binding::Key(KeyCode::Return)), Ok(None));
+      assert_eq!(
+        bindings.0[&Action::Confirm],
+        vec![Binding::Button(GamepadButtonType::South), Binding::Key(KeyCode::Return)]
+      );
+    }
+
+    #[test]
+    fn conflicting_rebind_swaps_bindings() {
+      let mut bindings = bindings(&[
+        (Action::Inventory, &[Binding::Key(KeyCode::I)]),
+        (Action::Equip, &[Binding::Key(KeyCode::E)]),
+      ]);
+
+      assert_eq!(bindings.rebind(Action::Inventory, Binding::Key(KeyCode::E)), Ok(Some(Action::Equip)));
+      assert_eq!(bindings.0[&Action::Inventory], vec![Binding::Key(KeyCode::E)]);
+      assert_eq!(bindings.0[&Action::Equip], vec![Binding::Key(KeyCode::I)]);
+    }
+
+    #[test]
+    fn conflicting_rebind_with_nothing_to_swap_is_rejected() {
+      let mut bindings = bindings(&[
+        (Action::Inventory, &[Binding::Button(GamepadButtonType::North)]),
+        (Action::Equip, &[Binding::Key(KeyCode::E)]),
+      ]);
+      let before = bindings.0.clone();
+
+      assert_eq!(bindings.rebind(Action::Inventory, Binding::Key(KeyCode::E)), Err(Action::Equip));
+      assert_eq!(bindings.0, before);
+    }
+}
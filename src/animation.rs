@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+pub struct AnimationPlugin;
+
+/// Which way a sprite is looking. Sheets without directions use one clip for all four.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Facing {
+  #[default]
+  Down,
+  Left,
+  Right,
+  Up
+}
+
+impl Facing {
+    pub const ALL: [Facing; 4] = [Facing::Down, Facing::Left, Facing::Right, Facing::Up];
+
+    /// The facing closest to `direction`; horizontal wins ties.
+    pub fn from_direction(direction: Vec2) -> Self {
+      if direction.x.abs() >= direction.y.abs() {
+        if direction.x < 0.0 { Facing::Left } else { Facing::Right }
+      } else if direction.y < 0.0 {
+        Facing::Down
+      } else {
+        Facing::Up
+      }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AnimationState {
+  #[default]
+  Idle,
+  Walk,
+  Hop,
+  Death
+}
+
+/// What a clip does after its last frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipEnd {
+  Loop,
+  /// Stays on the last frame and hides the sprite, e.g. once a death animation has played out.
+  Hide,
+  Then(AnimationState)
+}
+
+/// Atlas indices played in order, one per `SpriteAnimation::frame_time`.
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+  pub frames: Vec<usize>,
+  pub end: ClipEnd
+}
+
+impl AnimationClip {
+    /// `len` frames from `first` onwards, e.g. one row of a sheet.
+    pub fn row(first: usize, len: usize, end: ClipEnd) -> Self {
+      AnimationClip { frames: (first..first + len).collect(), end }
+    }
+}
+
+/// Plays clips from a `TextureAtlasSprite` sheet, picked by state and facing. Change them
+/// with `play` and `face`; the frame restarts whenever the clip changes.
+#[derive(Component, Clone, Debug)]
+pub struct SpriteAnimation {
+  clips: HashMap<(AnimationState, Facing), AnimationClip>,
+  state: AnimationState,
+  facing: Facing,
+  frame: usize,
+  timer: Timer,
+  finished: bool
+}
+
+impl SpriteAnimation {
+    pub fn new(frame_time: f32) -> Self {
+      SpriteAnimation {
+        clips: HashMap::new(),
+        state: AnimationState::Idle,
+        facing: Facing::Down,
+        frame: 0,
+        timer: Timer::from_seconds(frame_time, TimerMode::Repeating),
+        finished: false
+      }
+    }
+
+    /// Adds a clip for `state` when looking `facing`, or for every facing when `None`.
+    pub fn with_clip(mut self, state: AnimationState, facing: Option<Facing>, clip: AnimationClip) -> Self {
+      match facing {
+        Some(facing) => {
+          self.clips.insert((state, facing), clip);
+        }
+        None => {
+          for facing in Facing::ALL {
+            self.clips.insert((state, facing), clip.clone());
+          }
+        }
+      }
+      self
+    }
+
+    pub fn play(&mut self, state: AnimationState) {
+      if self.state != state {
+        self.state = state;
+        self.restart();
+      }
+    }
+
+    pub fn face(&mut self, facing: Facing) {
+      if self.facing != facing {
+        self.facing = facing;
+        self.restart();
+      }
+    }
+
+    /// Whether `state` is playing and has run out, e.g. a death clip that has hidden its
+    /// sprite. A state without a clip counts as finished straight away.
+    pub fn has_finished(&self, state: AnimationState) -> bool {
+      self.state == state && (self.finished || self.clip().is_none())
+    }
+
+    fn restart(&mut self) {
+      self.frame = 0;
+      self.finished = false;
+      self.timer.reset();
+    }
+
+    fn clip(&self) -> Option<&AnimationClip> {
+      self.clips.get(&(self.state, self.facing))
+    }
+
+    fn index(&self) -> Option<usize> {
+      let frames = &self.clip()?.frames;
+      frames.get(self.frame.min(frames.len().checked_sub(1)?)).copied()
+    }
+
+    /// Moves on to the next frame, applying the clip's `end` after the last one. Returns
+    /// `true` when a `ClipEnd::Hide` clip has just finished.
+    fn advance(&mut self) -> bool {
+      let Some(clip) = self.clip() else {
+        return false;
+      };
+      let (len, end) = (clip.frames.len(), clip.end);
+      if self.finished || self.frame + 1 < len {
+        self.frame = (self.frame + 1).min(len.saturating_sub(1));
+        return false;
+      }
+
+      match end {
+        ClipEnd::Loop => self.frame = 0,
+        ClipEnd::Hide => {
+          self.finished = true;
+          return true;
+        }
+        ClipEnd::Then(next) => self.play(next),
+      }
+      false
+    }
+}
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(animate_sprites);
+    }
+}
+
+fn animate_sprites(
+  time: Res<Time>,
+  mut sprite_query: Query<(&mut SpriteAnimation, &mut TextureAtlasSprite, Option<&mut Visibility>)>
+) {
+  for (mut animation, mut sprite, visibility) in &mut sprite_query {
+    if animation.timer.tick(time.delta()).just_finished() && animation.advance() {
+      if let Some(mut visibility) = visibility {
+        visibility.is_visible = false;
+      }
+    }
+
+    if let Some(index) = animation.index() {
+      if sprite.index != index {
+        sprite.index = index;
+      }
+    }
+  }
+}
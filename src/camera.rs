@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+use bevy::render::camera::Viewport;
+use bevy_ecs_ldtk::prelude::*;
+
+use crate::{AppState, VIEW_HEIGHT, VIEW_WIDTH, player::Player};
+
+/// How quickly the camera catches up with the player; higher is snappier.
+const FOLLOW_SPEED: f32 = 6.0;
+
+pub struct CameraPlugin;
+
+/// Where the camera was in the overworld before a fight moved it.
+#[derive(Resource, Default)]
+pub struct OverworldCamera(pub Option<Vec2>);
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<OverworldCamera>()
+        .add_system(fit_viewport_to_window)
+        .add_system_set(
+          SystemSet::on_exit(AppState::OverWorld).with_system(remember_overworld_camera)
+        )
+        .add_system_set(
+          SystemSet::on_enter(AppState::OverWorld).with_system(restore_overworld_camera)
+        )
+        .add_system_set(
+          SystemSet::on_update(AppState::OverWorld).with_system(follow_player)
+        );
+    }
+}
+
+/// Scales the view by the largest whole number that fits the window and centers it, leaving
+/// bars on the sides or top and bottom. Whole-number scaling keeps every texel the same size.
+fn fit_viewport_to_window(
+  windows: Res<Windows>,
+  mut camera_query: Query<(&mut Camera, &mut OrthographicProjection)>
+) {
+  let Some(window) = windows.get_primary() else {
+    return;
+  };
+  let window_size = UVec2::new(window.physical_width(), window.physical_height());
+  let view_size = Vec2::new(VIEW_WIDTH, VIEW_HEIGHT);
+  let scale = (window_size.as_vec2() / view_size).min_element().floor().max(1.0);
+  let size = (view_size * scale).as_uvec2().min(window_size);
+  let viewport = Viewport {
+    physical_position: (window_size - size) / 2,
+    physical_size: size,
+    ..default()
+  };
+
+  for (mut camera, mut projection) in &mut camera_query {
+    let unchanged = camera.viewport.as_ref().is_some_and(|current| {
+      current.physical_position == viewport.physical_position && current.physical_size == viewport.physical_size
+    });
+    if unchanged {
+      continue;
+    }
+    camera.viewport = Some(viewport.clone());
+    // Makes Bevy recompute the projection for the new viewport size.
+    projection.set_changed();
+  }
+}
+
+/// Eases the camera towards the player, keeping the view inside the current level. A level
+/// narrower or shorter than the view is centered instead.
+fn follow_player(
+  time: Res<Time>,
+  levels: Res<Assets<LdtkLevel>>,
+  player_query: Query<&Transform, (With<Player>, Without<Camera>)>,
+  level_query: Query<(&GlobalTransform, &Handle<LdtkLevel>)>,
+  mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<Camera>>
+) {
+  let (Ok(player_transform), Ok((mut camera_transform, projection))) =
+    (player_query.get_single(), camera_query.get_single_mut()) else {
+    return;
+  };
+
+  let mut target = player_transform.translation.truncate();
+  let level = level_query
+    .iter()
+    .find_map(|(transform, handle)| levels.get(handle).map(|level| (transform, &level.level)));
+  if let Some((level_transform, level)) = level {
+    let half_view = Vec2::new(
+      (projection.right - projection.left) * projection.scale,
+      (projection.top - projection.bottom) * projection.scale
+    ) / 2.0;
+    let min = level_transform.translation().truncate();
+    let max = min + Vec2::new(level.px_wid as f32, level.px_hei as f32);
+
+    target.x = clamp_axis(target.x, min.x, max.x, half_view.x);
+    target.y = clamp_axis(target.y, min.y, max.y, half_view.y);
+  }
+
+  let t = 1.0 - (-FOLLOW_SPEED * time.delta_seconds()).exp();
+  let position = camera_transform.translation.truncate().lerp(target, t);
+  camera_transform.translation.x = position.x;
+  camera_transform.translation.y = position.y;
+}
+
+fn clamp_axis(value: f32, min: f32, max: f32, half_view: f32) -> f32 {
+  if max - min <= half_view * 2.0 {
+    (min + max) / 2.0
+  } else {
+    value.clamp(min + half_view, max - half_view)
+  }
+}
+
+fn remember_overworld_camera(
+  mut overworld_camera: ResMut<OverworldCamera>,
+  camera_query: Query<&Transform, With<Camera>>
+) {
+  if let Ok(transform) = camera_query.get_single() {
+    overworld_camera.0 = Some(transform.translation.truncate());
+  }
+}
+
+fn restore_overworld_camera(
+  mut overworld_camera: ResMut<OverworldCamera>,
+  mut camera_query: Query<&mut Transform, With<Camera>>
+) {
+  let (Some(position), Ok(mut transform)) = (overworld_camera.0.take(), camera_query.get_single_mut()) else {
+    return;
+  };
+  transform.translation.x = position.x;
+  transform.translation.y = position.y;
+}
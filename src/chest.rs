@@ -0,0 +1,335 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::app::LdtkEntity;
+use bevy_ecs_ldtk::ldtk::{LayerInstance, TilesetDefinition};
+use bevy_ecs_ldtk::prelude::{FieldValue, GridCoords, LdtkLevel};
+use bevy_ecs_ldtk::EntityInstance;
+
+use crate::{TILE_SIZE, AppState, collider::Collider, notice::NoticeEvent, player::Player};
+use crate::actions::Action;
+use crate::equipment::{EquipmentBook, SpareEquipment};
+use crate::items::{Inventory, ItemBook};
+use crate::loot::{store_item, Wallet};
+use crate::map::level_identifier;
+
+const CHEST_TEXTURE: &str = "mystic_woods_free_v0.2/sprites/objects/chest_01.png";
+/// Frames in `chest_01.png`, closed to fully open.
+const CHEST_FRAMES: usize = 4;
+const CHEST_FRAME_TIME: f32 = 0.12;
+
+pub struct ChestPlugin;
+
+/// Marks a `Chest` from the LDtk file whose fields have not been read into a `ChestEntity` yet.
+#[derive(Clone, Debug, Default, Component)]
+pub struct Chest;
+
+#[derive(Clone, Debug, Default, Component)]
+pub struct ChestEntity {
+  /// LDtk instance id, used to remember the chest once opened.
+  pub iid: String,
+  pub items: Vec<String>,
+  pub gold: u32
+}
+
+impl ChestEntity {
+    pub fn from_entity_instance(entity_instance: &EntityInstance, level: &str) -> Result<Self, ChestError> {
+      let error = |field| ChestError {
+        level: level.to_string(),
+        iid: entity_instance.iid.clone(),
+        field
+      };
+      let field_value = |identifier: &str| {
+        entity_instance
+          .field_instances
+          .iter()
+          .find(|f| f.identifier == identifier)
+          .map(|f| &f.value)
+      };
+
+      let items = match field_value("Items") {
+        Some(FieldValue::Strings(items)) => items.iter().flatten().cloned().collect(),
+        Some(_) => return Err(error("Items")),
+        None => Vec::new(),
+      };
+
+      let gold = match field_value("Gold") {
+        Some(FieldValue::Int(Some(gold))) if *gold >= 0 => *gold as u32,
+        Some(FieldValue::Int(None)) | None => 0,
+        Some(_) => return Err(error("Gold")),
+      };
+
+      Ok(ChestEntity { iid: entity_instance.iid.clone(), items, gold })
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ChestError {
+  /// Identifier of the LDtk level the chest is in.
+  pub level: String,
+  pub iid: String,
+  pub field: &'static str
+}
+
+impl fmt::Display for ChestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(
+        f,
+        "Chest {} in level {}: \"{}\" field has the wrong type or a negative value",
+        self.iid,
+        self.level,
+        self.field
+      )
+    }
+}
+
+impl std::error::Error for ChestError {}
+
+#[derive(Bundle)]
+pub struct ChestBundle {
+    chest: Chest,
+    entity_instance: EntityInstance,
+    grid_coords: GridCoords,
+    collider: Collider,
+    #[bundle]
+    sprite_sheet_bundle: SpriteSheetBundle,
+}
+
+impl LdtkEntity for ChestBundle {
+    fn bundle_entity(
+      entity_instance: &EntityInstance,
+      layer_instance: &LayerInstance,
+      tileset: Option<&Handle<Image>>,
+      _: Option<&TilesetDefinition>,
+      asset_server: &AssetServer,
+      texture_atlases: &mut Assets<TextureAtlas>,
+    ) -> Self {
+      let texture = tileset.cloned().unwrap_or_else(|| asset_server.load(CHEST_TEXTURE));
+      let texture_atlas = TextureAtlas::from_grid(texture, Vec2::splat(TILE_SIZE), CHEST_FRAMES, 1, None, None);
+
+      ChestBundle {
+        chest: Chest,
+        entity_instance: entity_instance.clone(),
+        grid_coords: GridCoords::from_entity_info(entity_instance, layer_instance),
+        collider: Collider,
+        sprite_sheet_bundle: SpriteSheetBundle {
+          texture_atlas: texture_atlases.add(texture_atlas),
+          ..default()
+        },
+      }
+    }
+}
+
+/// Instance ids of every chest the player has opened. Chests are rebuilt from the LDtk
+/// file on level reloads, so this is what keeps them open (and empty).
+#[derive(Resource, Clone, Debug, Default)]
+pub struct OpenedChests(pub HashSet<String>);
+
+/// Plays the lid animation, one frame per tick.
+#[derive(Component)]
+struct ChestOpening(Timer);
+
+impl Plugin for ChestPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<OpenedChests>()
+        .add_system(parse_chests)
+        .add_system(restore_opened_chests.after(parse_chests))
+        .add_system(animate_chest_opening)
+        .add_system_set(
+          SystemSet::on_update(AppState::OverWorld).with_system(open_chest)
+        );
+    }
+}
+
+/// Reads the fields of each new `Chest` into a `ChestEntity`. A chest with bad fields is
+/// logged and removed so the rest of the level still loads.
+#[allow(clippy::type_complexity)]
+fn parse_chests(
+  mut commands: Commands,
+  levels: Res<Assets<LdtkLevel>>,
+  level_query: Query<&Handle<LdtkLevel>>,
+  chest_query: Query<(Entity, &EntityInstance, &Parent), (With<Chest>, Added<EntityInstance>)>
+) {
+  for (entity, entity_instance, parent) in chest_query.iter() {
+    let level = level_identifier(parent, &level_query, &levels);
+    match ChestEntity::from_entity_instance(entity_instance, level) {
+      Ok(chest) => {
+        commands.entity(entity).insert(chest);
+      }
+      Err(error) => {
+        error!("Skipping invalid chest! {}", error);
+        commands.entity(entity).despawn_recursive();
+      }
+    }
+  }
+}
+
+/// Keeps chests that are not mid-animation showing open or closed to match `OpenedChests`,
+/// e.g. after a level reload or loading a save.
+fn restore_opened_chests(
+  opened: Res<OpenedChests>,
+  mut chest_query: Query<(&ChestEntity, &mut TextureAtlasSprite), Without<ChestOpening>>
+) {
+  for (chest, mut sprite) in &mut chest_query {
+    let index = if opened.0.contains(&chest.iid) { CHEST_FRAMES - 1 } else { 0 };
+    if sprite.index != index {
+      sprite.index = index;
+    }
+  }
+}
+
+/// `Confirm` next to a closed chest opens it and hands its contents to the player.
+#[allow(clippy::too_many_arguments)]
+fn open_chest(
+  mut commands: Commands,
+  actions: Res<Input<Action>>,
+  item_book: Res<ItemBook>,
+  equipment_book: Res<EquipmentBook>,
+  mut opened: ResMut<OpenedChests>,
+  mut player_query: Query<(&Transform, &mut Inventory, &mut SpareEquipment, &mut Wallet), With<Player>>,
+  chest_query: Query<(Entity, &ChestEntity, &GridCoords)>,
+  mut notice_event: EventWriter<NoticeEvent>
+) {
+  if !actions.just_pressed(Action::Confirm) {
+    return;
+  }
+  let Ok((transform, mut inventory, mut spare, mut wallet)) = player_query.get_single_mut() else {
+    return;
+  };
+
+  let player_grid = (transform.translation.truncate() / TILE_SIZE).round();
+  let Some((entity, chest)) = chest_query
+    .iter()
+    .filter(|(_, chest, _)| !opened.0.contains(&chest.iid))
+    .find(|(_, _, grid_coords)| {
+      let offset = Vec2::new(grid_coords.x as f32, grid_coords.y as f32) - player_grid;
+      offset.abs().max_element() <= 1.0
+    })
+    .map(|(entity, chest, _)| (entity, chest)) else {
+    return;
+  };
+
+  opened.0.insert(chest.iid.clone());
+  commands
+    .entity(entity)
+    .insert(ChestOpening(Timer::from_seconds(CHEST_FRAME_TIME, TimerMode::Repeating)));
+
+  let mut found = Vec::new();
+  if chest.gold > 0 {
+    wallet.gold += chest.gold;
+    found.push(format!("{} gold", chest.gold));
+  }
+  for item in &chest.items {
+    if store_item(item, &item_book, &equipment_book, &mut inventory, &mut spare) {
+      found.push(item.clone());
+    } else {
+      warn!("Chest {} holds unknown item {}", chest.iid, item);
+    }
+  }
+
+  let message = if found.is_empty() {
+    "The chest is empty".to_string()
+  } else {
+    format!("Found {}", found.join(", "))
+  };
+  notice_event.send(NoticeEvent(message));
+}
+
+fn animate_chest_opening(
+  mut commands: Commands,
+  time: Res<Time>,
+  mut chest_query: Query<(Entity, &mut ChestOpening, &mut TextureAtlasSprite)>
+) {
+  for (entity, mut opening, mut sprite) in &mut chest_query {
+    if !opening.0.tick(time.delta()).just_finished() {
+      continue;
+    }
+
+    sprite.index += 1;
+    if sprite.index >= CHEST_FRAMES - 1 {
+      sprite.index = CHEST_FRAMES - 1;
+      commands.entity(entity).remove::<ChestOpening>();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_ldtk::ldtk::FieldInstance;
+
+    use super::*;
+
+    fn field(identifier: &str, value: FieldValue) -> FieldInstance {
+      FieldInstance {
+        identifier: identifier.to_string(),
+        tile: None,
+        field_instance_type: String::new(),
+        value,
+        def_uid: 0,
+        real_editor_values: Vec::new()
+      }
+    }
+
+    fn chest_instance(fields: Vec<FieldInstance>) -> EntityInstance {
+      EntityInstance {
+        iid: "chest-iid".to_string(),
+        field_instances: fields,
+        ..default()
+      }
+    }
+
+    fn error_field(instance: &EntityInstance) -> &'static str {
+      ChestEntity::from_entity_instance(instance, "Level_0").unwrap_err().field
+    }
+
+    #[test]
+    fn missing_fields_make_an_empty_chest() {
+      let chest = ChestEntity::from_entity_instance(&chest_instance(vec![]), "Level_0").unwrap();
+
+      assert_eq!(chest.iid, "chest-iid");
+      assert!(chest.items.is_empty());
+      assert_eq!(chest.gold, 0);
+
+      let empty_gold = chest_instance(vec![field("Gold", FieldValue::Int(None))]);
+      assert_eq!(ChestEntity::from_entity_instance(&empty_gold, "Level_0").unwrap().gold, 0);
+    }
+
+    #[test]
+    fn parses_items_and_gold() {
+      let instance = chest_instance(vec![
+        field("Items", FieldValue::Strings(vec![Some("Potion".to_string()), None, Some("Ether".to_string())])),
+        field("Gold", FieldValue::Int(Some(25))),
+      ]);
+      let chest = ChestEntity::from_entity_instance(&instance, "Level_0").unwrap();
+
+      assert_eq!(chest.items, vec!["Potion".to_string(), "Ether".to_string()]);
+      assert_eq!(chest.gold, 25);
+    }
+
+    #[test]
+    fn rejects_wrong_field_types() {
+      let items = chest_instance(vec![field("Items", FieldValue::String(Some("Potion".to_string())))]);
+      assert_eq!(error_field(&items), "Items");
+
+      let gold = chest_instance(vec![field("Gold", FieldValue::Float(Some(2.5)))]);
+      assert_eq!(error_field(&gold), "Gold");
+    }
+
+    #[test]
+    fn rejects_negative_gold() {
+      let instance = chest_instance(vec![field("Gold", FieldValue::Int(Some(-5)))]);
+      assert_eq!(error_field(&instance), "Gold");
+    }
+
+    #[test]
+    fn error_message_locates_the_chest() {
+      let instance = chest_instance(vec![field("Gold", FieldValue::Int(Some(-5)))]);
+      let error = ChestEntity::from_entity_instance(&instance, "Level_3").unwrap_err();
+      assert_eq!(
+        error.to_string(),
+        "Chest chest-iid in level Level_3: \"Gold\" field has the wrong type or a negative value"
+      );
+    }
+}
@@ -1,16 +1,132 @@
-use bevy::{prelude::*, input::keyboard};
+use bevy::prelude::*;
 
-use crate::{AppState, enemy::Enemy, combat_stats::CombatStats, player::Player};
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+
+use crate::{AppState, enemy::{slime_animation, slime_texture_atlas, DefeatedEnemies, Enemy, EnemySpawnEntity}, combat_stats::{CombatStats, EffectiveStats}, party::Party};
+#[cfg(feature = "debug")]
+use crate::actions::Action;
+use crate::animation::{AnimationState, SpriteAnimation};
+use crate::combat_result::VictorySummary;
+use crate::damage::{Affinities, Affinity, DamageContext, DamagePipeline, DamageResult, Element};
+use crate::experience::ExperienceReward;
+use crate::loot::Loot;
+use crate::rng::GameRng;
+use crate::status::{StatusEffect, StatusEffects};
+
+const ENEMY_TURN_DELAY: f32 = 0.8;
+const BASE_ESCAPE_CHANCE: f32 = 0.5;
+const ESCAPE_CHANCE_PER_POINT: f32 = 0.1;
+const ENEMY_SPACING: f32 = 48.0;
+const ENEMY_X: f32 = -80.0;
 
 pub struct CombatPlugin;
 
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FightEffect {
+  Damage,
+  Heal,
+  /// Damage that skips the `DamagePipeline`, e.g. poison.
+  Direct,
+  /// Only applies the event's status.
+  Status
+}
+
 pub struct FightEvent {
+  pub(crate) attacker: Entity,
   pub(crate) target: Entity,
-  pub(crate) damage_amount: isize
+  pub(crate) damage_amount: isize,
+  pub(crate) element: Element,
+  pub(crate) effect: FightEffect,
+  /// Applied to the target afterwards if it is still standing.
+  pub(crate) status: Option<StatusEffect>
+}
+
+impl FightEvent {
+    /// A plain physical attack.
+    pub fn attack(attacker: Entity, target: Entity, damage_amount: isize) -> Self {
+      FightEvent {
+        attacker,
+        target,
+        damage_amount,
+        element: Element::Physical,
+        effect: FightEffect::Damage,
+        status: None
+      }
+    }
 }
 
 pub struct EnterCombatEvent {
+  pub(crate) overworld_enemy: Entity,
+  pub(crate) enemy_spawn: EnemySpawnEntity
+}
 
+/// The overworld enemy the current fight was started from.
+#[derive(Resource)]
+pub struct Encounter {
+  pub overworld_enemy: Entity,
+  /// Instance id of the `Enemy_Spawn` it came from.
+  pub spawn_iid: String,
+  pub escapable: bool
+}
+
+/// Who acts next in the current fight: each party member in party order, then each enemy in
+/// spawn order. The order is fixed for the whole fight rather than rolled from a speed stat,
+/// so it is always clear whose turn comes next.
+#[derive(Resource, Default)]
+pub struct TurnOrder {
+  combatants: Vec<Entity>,
+  current: usize
+}
+
+impl TurnOrder {
+    pub fn active(&self) -> Option<Entity> {
+      self.combatants.get(self.current).copied()
+    }
+
+    pub fn end_turn(&mut self) {
+      if !self.combatants.is_empty() {
+        self.current = (self.current + 1) % self.combatants.len();
+      }
+    }
+
+    /// Drops a defeated combatant, keeping whoever is up next unchanged.
+    pub fn remove(&mut self, entity: Entity) {
+      let Some(index) = self.combatants.iter().position(|&combatant| combatant == entity) else {
+        return;
+      };
+
+      self.combatants.remove(index);
+      if index < self.current {
+        self.current -= 1;
+      }
+      if self.current >= self.combatants.len() {
+        self.current = 0;
+      }
+    }
+
+    fn clear(&mut self) {
+      self.combatants.clear();
+      self.current = 0;
+    }
+}
+
+/// Set once the last enemy falls; the fight ends when their death animations have played.
+#[derive(Resource, Default)]
+pub struct PendingVictory(pub bool);
+
+/// Short pause before an enemy acts so its turn is readable on screen.
+#[derive(Resource)]
+struct EnemyTurnTimer(Timer);
+
+impl Default for EnemyTurnTimer {
+    fn default() -> Self {
+      EnemyTurnTimer(Timer::from_seconds(ENEMY_TURN_DELAY, TimerMode::Once))
+    }
+}
+
+enum EnemyAction {
+  Attack { target: Entity }
 }
 
 impl Plugin for CombatPlugin {
@@ -18,36 +134,278 @@ impl Plugin for CombatPlugin {
         app
           .add_event::<FightEvent>()
           .add_event::<EnterCombatEvent>()
+          .add_event::<DamageResult>()
+          .init_resource::<DamagePipeline>()
+          .init_resource::<TurnOrder>()
+          .init_resource::<EnemyTurnTimer>()
+          .init_resource::<PendingVictory>()
         .add_system_set(
           SystemSet::on_update(AppState::Combat)
             .with_system(combat_camera)
+            .with_system(build_turn_order)
+            .with_system(enemy_turn)
             .with_system(damage_calculation)
+            .with_system(log_damage.after(damage_calculation))
+            .with_system(finish_victory.after(damage_calculation))
         )
         .add_system_set(
-          SystemSet::on_exit(AppState::Combat).with_system(despawn_enemy)
+          SystemSet::on_exit(AppState::Combat)
+            .with_system(despawn_enemy)
+            .with_system(end_encounter)
+            .with_system(clear_turn_order)
+            .with_system(clear_pending_victory)
         )
-        .add_system(enter_combat)
-        .add_system(leave_combat);
+        .add_system(enter_combat);
+
+        #[cfg(feature = "debug")]
+        app.add_system_set(
+          SystemSet::on_update(AppState::Combat).with_system(leave_combat)
+        );
     }
 }
 
+/// Even odds against an equal foe, shifted by 10% for every point of attack plus
+/// defense the fleeing member has over the strongest enemy still standing.
+pub fn escape_chance<'a>(
+  player_stats: &CombatStats,
+  enemy_stats: impl Iterator<Item = &'a CombatStats>
+) -> f32 {
+  let player_power = player_stats.attack + player_stats.defense;
+  let enemy_power = enemy_stats
+    .filter(|stats| stats.health > 0)
+    .map(|stats| stats.attack + stats.defense)
+    .max()
+    .unwrap_or(0);
+
+  (BASE_ESCAPE_CHANCE + (player_power - enemy_power) as f32 * ESCAPE_CHANCE_PER_POINT)
+    .clamp(0.05, 0.95)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn damage_calculation(
   mut fight_event: EventReader<FightEvent>,
+  mut damage_result: EventWriter<DamageResult>,
+  pipeline: Res<DamagePipeline>,
+  mut rng: ResMut<GameRng>,
   mut target_query: Query<&mut CombatStats>,
+  mut effects_query: Query<&mut StatusEffects>,
+  affinity_query: Query<&Affinities>,
+  effective_query: Query<&EffectiveStats>,
+  mut turn_order: ResMut<TurnOrder>,
+  mut enemy_animation_query: Query<&mut SpriteAnimation, With<Enemy>>,
+  enemy_query: Query<Entity, With<Enemy>>,
+  party: Res<Party>,
+  mut pending_victory: ResMut<PendingVictory>,
   mut state: ResMut<State<AppState>>
 ) {
+  let mut damage_dealt = false;
   for event in fight_event.iter() {
-    let mut target_stats = target_query.get_mut(event.target).expect("Fighting target without stats!");
+    let Ok(mut target_stats) = target_query.get_mut(event.target) else {
+      continue;
+    };
 
-    target_stats.health = std::cmp::max(
-      target_stats.health - (event.damage_amount - target_stats.defense),
-      0
-    );
+    if event.effect == FightEffect::Status {
+      apply_status(event, target_stats.health > 0, &mut effects_query);
+      continue;
+    }
+
+    let attacker = effective_query.get(event.attacker).copied().unwrap_or_default();
+    let target = effective_query.get(event.target).copied().unwrap_or_else(|_| EffectiveStats::base(&target_stats));
+    // Statuses are already part of the effective stats; hand them to the `Buffs` stage instead.
+    let mut context = DamageContext::new(event.damage_amount - attacker.attack_bonus, target.defense - target.defense_bonus);
+    context.attack_bonus = attacker.attack_bonus;
+    context.defense_bonus = target.defense_bonus;
+    let amount = match event.effect {
+      FightEffect::Damage => {
+        if let Ok(affinities) = affinity_query.get(event.target) {
+          context.affinity = affinities.get(event.element);
+        }
+        pipeline.run(&mut context, &mut *rng);
+        context.final_amount()
+      }
+      _ => event.damage_amount
+    };
+
+    if event.effect == FightEffect::Heal || context.affinity == Affinity::Absorb {
+      let amount = target_stats.heal(amount);
+      damage_result.send(DamageResult {
+        attacker: event.attacker,
+        target: event.target,
+        amount,
+        element: event.element,
+        affinity: context.affinity,
+        heal: true,
+        critical: context.critical,
+        remaining_health: target_stats.health
+      });
+      apply_status(event, target_stats.health > 0, &mut effects_query);
+      continue;
+    }
+
+    target_stats.health = std::cmp::max(target_stats.health - amount, 0);
+    damage_dealt = true;
+    damage_result.send(DamageResult {
+      attacker: event.attacker,
+      target: event.target,
+      amount,
+      element: event.element,
+      affinity: context.affinity,
+      heal: false,
+      critical: context.critical,
+      remaining_health: target_stats.health
+    });
+    apply_status(event, target_stats.health > 0, &mut effects_query);
 
     if target_stats.health == 0 {
-      state.set(AppState::OverWorld).unwrap();
+      turn_order.remove(event.target);
+
+      let party_defeated = party
+        .members
+        .iter()
+        .all(|&member| target_query.get(member).map_or(true, |stats| stats.health == 0));
+      if party.contains(event.target) && party_defeated {
+        state.set(AppState::GameOver).unwrap();
+        return;
+      }
+
+      if let Ok(mut animation) = enemy_animation_query.get_mut(event.target) {
+        animation.play(AnimationState::Death);
+      }
     }
   }
+
+  let enemies_defeated = enemy_query
+    .iter()
+    .all(|enemy| target_query.get(enemy).map_or(true, |stats| stats.health == 0));
+
+  if damage_dealt && enemies_defeated {
+    pending_victory.0 = true;
+  }
+}
+
+/// Moves on to the victory screen once every enemy's death animation has finished, so the
+/// killing blow is seen to land.
+fn finish_victory(
+  mut commands: Commands,
+  mut pending_victory: ResMut<PendingVictory>,
+  animation_query: Query<&SpriteAnimation, With<Enemy>>,
+  enemy_query: Query<(&Name, &ExperienceReward, Option<&Loot>), With<Enemy>>,
+  encounter: Option<Res<Encounter>>,
+  mut defeated: ResMut<DefeatedEnemies>,
+  mut state: ResMut<State<AppState>>
+) {
+  if !pending_victory.0 || !animation_query.iter().all(|animation| animation.has_finished(AnimationState::Death)) {
+    return;
+  }
+
+  pending_victory.0 = false;
+  if let Some(encounter) = &encounter {
+    commands.entity(encounter.overworld_enemy).despawn_recursive();
+    defeated.0.insert(encounter.spawn_iid.clone());
+  }
+  commands.insert_resource(VictorySummary {
+    defeated: enemy_query.iter().map(|(name, _, _)| name.to_string()).collect(),
+    experience: enemy_query.iter().map(|(_, reward, _)| reward.0).sum(),
+    loot_tables: enemy_query.iter().filter_map(|(_, _, loot)| loot.map(|loot| loot.0.clone())).collect(),
+    ..default()
+  });
+  state.set(AppState::Victory).unwrap();
+}
+
+fn clear_pending_victory(mut pending_victory: ResMut<PendingVictory>) {
+  pending_victory.0 = false;
+}
+
+/// Adds the event's status once its hit has landed, unless that hit took the target down.
+fn apply_status(event: &FightEvent, standing: bool, effects_query: &mut Query<&mut StatusEffects>) {
+  let Some(status) = event.status.filter(|_| standing) else {
+    return;
+  };
+  if let Ok(mut effects) = effects_query.get_mut(event.target) {
+    effects.add(status);
+  }
+}
+
+fn log_damage(mut damage_result: EventReader<DamageResult>, name_query: Query<&Name>) {
+  for result in damage_result.iter() {
+    let name = |entity| name_query.get(entity).map_or("?".to_string(), |name| name.to_string());
+    if result.heal {
+      info!("{} heals {} for {}", name(result.attacker), name(result.target), result.amount);
+      continue;
+    }
+    info!(
+      "{} hits {} for {}{} ({} health left)",
+      name(result.attacker),
+      name(result.target),
+      result.amount,
+      if result.critical { " (critical)" } else { "" },
+      result.remaining_health
+    );
+  }
+}
+
+fn build_turn_order(
+  mut turn_order: ResMut<TurnOrder>,
+  party: Res<Party>,
+  stats_query: Query<&CombatStats>,
+  enemy_query: Query<Entity, With<Enemy>>
+) {
+  if !turn_order.combatants.is_empty() || enemy_query.is_empty() {
+    return;
+  }
+
+  turn_order.combatants.extend(party.living_members(&stats_query));
+  turn_order.combatants.extend(enemy_query.iter());
+}
+
+fn clear_turn_order(mut turn_order: ResMut<TurnOrder>, mut timer: ResMut<EnemyTurnTimer>) {
+  turn_order.clear();
+  timer.0.reset();
+}
+
+fn choose_enemy_action(targets: &[Entity], rng: &mut GameRng) -> Option<EnemyAction> {
+  targets
+    .choose(rng)
+    .map(|&target| EnemyAction::Attack { target })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn enemy_turn(
+  time: Res<Time>,
+  mut timer: ResMut<EnemyTurnTimer>,
+  mut turn_order: ResMut<TurnOrder>,
+  mut rng: ResMut<GameRng>,
+  party: Res<Party>,
+  stats_query: Query<&CombatStats>,
+  enemy_query: Query<&EffectiveStats, With<Enemy>>,
+  mut animation_query: Query<&mut SpriteAnimation, With<Enemy>>,
+  mut fight_event: EventWriter<FightEvent>
+) {
+  let Some((enemy, enemy_stats)) = turn_order
+    .active()
+    .and_then(|active| enemy_query.get(active).ok().map(|stats| (active, stats))) else {
+    return;
+  };
+
+  if !timer.0.tick(time.delta()).finished() {
+    return;
+  }
+  timer.0.reset();
+
+  match choose_enemy_action(&party.living_members(&stats_query), &mut rng) {
+    Some(EnemyAction::Attack { target }) => {
+      fight_event.send(FightEvent::attack(enemy, target, enemy_stats.attack));
+      if let Ok(mut animation) = animation_query.get_mut(enemy) {
+        animation.play(AnimationState::Hop);
+      }
+    }
+    None => {}
+  }
+  turn_order.end_turn();
+}
+
+fn end_encounter(mut commands: Commands) {
+  commands.remove_resource::<Encounter>();
 }
 
 fn despawn_enemy(
@@ -68,44 +426,192 @@ fn combat_camera(mut camera_query: Query<&mut Transform, With<Camera>>) {
 
 fn enter_combat (
   mut enter_combat_event: EventReader<EnterCombatEvent>,
-  mut keyboard: ResMut<Input<KeyCode>>,
   mut state: ResMut<State<AppState>>,
   mut commands: Commands,
-  mut asset_server: Res<AssetServer>,
+  asset_server: Res<AssetServer>,
   mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
-  for event in enter_combat_event.iter() {
-    state.set(AppState::Combat).unwrap();
-
-    let texture_handle = asset_server.load("mystic_woods_free_v0.2/sprites/characters/slime.png");
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(32.0, 32.0), 5, 7, None, None);
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
-    commands
-        .spawn(SpriteSheetBundle {
-            texture_atlas: texture_atlas_handle,
-              transform: Transform {
-                translation: Vec3::new(0.0,0.0, 900.0),
-                ..Default::default()
-            },
-            ..default()
-        })
-        .insert(Enemy)
-        .insert(CombatStats {
-            health: 3,
-            max_health: 3,
-            attack: 2,
-            defense: 1,
-        });
+  if let Some(event) = enter_combat_event.iter().last() {
+    let enemy_spawn = &event.enemy_spawn;
+    // Takes priority over a menu opened on the same frame.
+    state.overwrite_set(AppState::Combat).unwrap();
+    commands.insert_resource(Encounter {
+      overworld_enemy: event.overworld_enemy,
+      spawn_iid: enemy_spawn.iid.clone(),
+      escapable: !enemy_spawn.unescapable
+    });
+
+    let texture_atlas_handle = texture_atlases.add(slime_texture_atlas(&asset_server));
+    for index in 0..enemy_spawn.count {
+      let name = if enemy_spawn.count > 1 {
+        format!("{} {}", enemy_spawn.name, (b'A' + index as u8) as char)
+      } else {
+        enemy_spawn.name.clone()
+      };
+      let x = ENEMY_X + (index as f32 - (enemy_spawn.count - 1) as f32 / 2.0) * ENEMY_SPACING;
+      let stats = enemy_spawn.combat_stats();
+
+      let mut enemy = commands
+          .spawn(SpriteSheetBundle {
+              texture_atlas: texture_atlas_handle.clone(),
+                transform: Transform {
+                  translation: Vec3::new(x, 0.0, 900.0),
+                  ..Default::default()
+              },
+              ..default()
+          });
+      enemy
+          .insert(Name::new(name))
+          .insert(Enemy)
+          .insert(slime_animation())
+          .insert(ExperienceReward(enemy_spawn.experience))
+          .insert(EffectiveStats::base(&stats))
+          .insert(stats)
+          .insert(enemy_spawn.affinities.clone())
+          .insert(StatusEffects::default());
+      if let Some(loot) = &enemy_spawn.loot {
+        enemy.insert(Loot(loot.clone()));
+      }
+    }
   }
 }
 
+#[cfg(feature = "debug")]
 fn leave_combat(
-  mut keyboard: ResMut<Input<KeyCode>>,
+  mut actions: ResMut<Input<Action>>,
   mut state: ResMut<State<AppState>>
 ) {
-  if keyboard.just_pressed(KeyCode::M) {
+  if actions.just_pressed(Action::LeaveCombat) {
     state.set(AppState::OverWorld).unwrap();
 
-    keyboard.clear()
+    actions.clear()
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::AssetPlugin;
+
+    use crate::status::StatusKind;
+
+    use super::*;
+
+    fn combat_app() -> App {
+      let mut app = App::new();
+      app
+        .add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin::default())
+        .add_asset::<TextureAtlas>()
+        .add_state(AppState::Combat)
+        .insert_resource(GameRng::from_seed(0))
+        .init_resource::<Party>()
+        .init_resource::<DefeatedEnemies>()
+        .add_plugin(CombatPlugin);
+      #[cfg(feature = "debug")]
+      app.init_resource::<Input<Action>>();
+      app.world.spawn((Camera::default(), Transform::default()));
+      app
+    }
+
+    fn stats(health: isize) -> CombatStats {
+      CombatStats {
+        health,
+        max_health: health,
+        attack: 5,
+        defense: 0,
+        mana: 0,
+        max_mana: 0
+      }
+    }
+
+    fn spawn_member(app: &mut App, health: isize) -> Entity {
+      let member = app.world.spawn((Name::new("Member"), stats(health))).id();
+      app.world.resource_mut::<Party>().members.push(member);
+      member
+    }
+
+    fn spawn_enemy(app: &mut App, health: isize) -> Entity {
+      app.world.spawn((Name::new("Slime"), Enemy, ExperienceReward(1), stats(health))).id()
+    }
+
+    /// Sends one hit through a frame and returns the state the fight is in afterwards.
+    fn hit(app: &mut App, attacker: Entity, target: Entity, damage_amount: isize) -> AppState {
+      app.world.send_event(FightEvent::attack(attacker, target, damage_amount));
+      app.update();
+      app.world.resource::<State<AppState>>().current().clone()
+    }
+
+    #[test]
+    fn victory_once_the_last_enemy_dies() {
+      let mut app = combat_app();
+      let member = spawn_member(&mut app, 10);
+      let first = spawn_enemy(&mut app, 5);
+      let second = spawn_enemy(&mut app, 5);
+
+      assert_eq!(hit(&mut app, member, first, 100), AppState::Combat);
+      assert_eq!(hit(&mut app, member, second, 100), AppState::Victory);
+      assert_eq!(app.world.resource::<VictorySummary>().defeated, vec!["Slime", "Slime"]);
+    }
+
+    #[test]
+    fn victory_waits_for_the_death_animation() {
+      let mut app = combat_app();
+      let member = spawn_member(&mut app, 10);
+      let enemy = spawn_enemy(&mut app, 5);
+      app.world.entity_mut(enemy).insert(slime_animation());
+
+      assert_eq!(hit(&mut app, member, enemy, 100), AppState::Combat);
+      app.update();
+      assert_eq!(*app.world.resource::<State<AppState>>().current(), AppState::Combat);
+      assert!(app.world.resource::<PendingVictory>().0);
+      assert!(!app.world.get::<SpriteAnimation>(enemy).unwrap().has_finished(AnimationState::Death));
+
+      // Stands in for the clip running out; the animation system is not part of this app.
+      app.world.entity_mut(enemy).remove::<SpriteAnimation>();
+      app.update();
+      assert_eq!(*app.world.resource::<State<AppState>>().current(), AppState::Victory);
+    }
+
+    #[test]
+    fn game_over_only_once_every_member_is_down() {
+      let mut app = combat_app();
+      let first = spawn_member(&mut app, 5);
+      let second = spawn_member(&mut app, 5);
+      let enemy = spawn_enemy(&mut app, 10);
+
+      assert_eq!(hit(&mut app, enemy, first, 100), AppState::Combat);
+      assert_eq!(app.world.get::<CombatStats>(first).unwrap().health, 0);
+      assert_eq!(hit(&mut app, enemy, second, 100), AppState::GameOver);
+    }
+
+    #[test]
+    fn no_transition_while_anyone_is_standing() {
+      let mut app = combat_app();
+      let member = spawn_member(&mut app, 100);
+      let enemy = spawn_enemy(&mut app, 100);
+
+      assert_eq!(hit(&mut app, member, enemy, 5), AppState::Combat);
+      assert_eq!(hit(&mut app, enemy, member, 5), AppState::Combat);
+      assert!(app.world.get::<CombatStats>(member).unwrap().health > 0);
+      assert!(app.world.get::<CombatStats>(enemy).unwrap().health > 0);
+    }
+
+    #[test]
+    fn status_only_sticks_to_targets_left_standing() {
+      let mut app = combat_app();
+      let member = spawn_member(&mut app, 10);
+      let survivor = spawn_enemy(&mut app, 100);
+      let victim = spawn_enemy(&mut app, 5);
+      app.world.entity_mut(survivor).insert(StatusEffects::default());
+      app.world.entity_mut(victim).insert(StatusEffects::default());
+      let poison = StatusEffect { kind: StatusKind::Poison, turns: 3, potency: 1 };
+
+      for (target, damage_amount) in [(survivor, 5), (victim, 100)] {
+        app.world.send_event(FightEvent { status: Some(poison), ..FightEvent::attack(member, target, damage_amount) });
+      }
+      app.update();
+
+      assert!(app.world.get::<StatusEffects>(survivor).unwrap().has(StatusKind::Poison));
+      assert!(!app.world.get::<StatusEffects>(victim).unwrap().has(StatusKind::Poison));
+    }
+}
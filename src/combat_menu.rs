@@ -1,7 +1,20 @@
-use bevy::{prelude::*, input::keyboard};
+use bevy::{prelude::*, ecs::schedule::ShouldRun};
 use bevy_inspector_egui::Inspectable;
+use rand::Rng;
 
-use crate::{AppState, combat_stats::{CombatStats, self}, enemy::Enemy, combat::FightEvent, player::Player};
+use crate::{AppState, FONT, actions::Action, combat_stats::{CombatStats, EffectiveStats}, enemy::Enemy, combat::{escape_chance, Encounter, FightEffect, FightEvent, PendingVictory, TurnOrder}, damage::{Affinity, DamageResult, Element}, party::{Party, PartyBattler}, rng::GameRng};
+use crate::skills::{KnownSkills, SkillBook, SkillTarget};
+use crate::status::StatusEffects;
+use crate::items::{Inventory, ItemUse, ItemUsedEvent};
+use crate::player::Player;
+use crate::ui::{spawn_button, spawn_text, ButtonLayout, FONT_SIZE};
+
+const COMMAND_BUTTON: ButtonLayout = ButtonLayout {
+  width: Val::Percent(10.),
+  height: Val::Px(30.),
+  margin: Val::Px(0.),
+  font_size: FONT_SIZE
+};
 
 pub struct CombatMenuPlugin;
 
@@ -11,26 +24,107 @@ pub struct CombatMenu;
 #[derive(Component, Inspectable)]
 pub struct EnemyHealthText;
 
+#[derive(Component, Inspectable)]
+pub struct PartyHealthText;
+
+#[derive(Component, Inspectable)]
+pub struct CombatLogText;
+
 #[derive(Component, Inspectable)]
 pub struct AttackButton;
 
 #[derive(Component, Inspectable)]
 pub struct RunButton;
 
+#[derive(Component, Inspectable)]
+pub struct SkillsButton;
+
+/// Skill or item list opened from the command menu for the member acting at the time.
+#[derive(Component)]
+pub struct CommandSubmenu {
+  pub member: Entity
+}
+
+#[derive(Component)]
+pub struct SkillButton {
+  pub skill: String,
+  pub cost: isize
+}
+
+#[derive(Component, Inspectable)]
+pub struct SubmenuBackButton;
+
+#[derive(Component, Inspectable)]
+pub struct ItemsButton;
+
+#[derive(Component)]
+pub struct ItemButton {
+  pub item: String
+}
+
+const ENEMY_SIZE: f32 = 32.0;
+const BATTLER_SIZE: f32 = 48.0;
+const SELECTED_TINT: Color = Color::rgb(1.0, 0.85, 0.3);
+
+/// What happens to the target once the selection is confirmed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum PendingAction {
+  #[default]
+  Attack,
+  Skill(String),
+  Item(String)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TargetSide {
+  #[default]
+  Enemies,
+  Party
+}
+
+/// Set after choosing Attack or a single-target skill while the player picks who it hits.
+#[derive(Resource, Default)]
+pub struct TargetSelection {
+  pub active: bool,
+  pub selected: Option<Entity>,
+  pub action: PendingAction,
+  pub side: TargetSide
+}
+
 impl Plugin for CombatMenuPlugin {
     fn build(&self, app: &mut App) {
         app
+        .init_resource::<TargetSelection>()
         .add_system_set(
           SystemSet::on_enter(AppState::Combat)
             .with_system(build_combat_menu)
         )
         .add_system_set(
-          SystemSet::on_exit(AppState::Combat).with_system(despawn_combat_menu)
+          SystemSet::on_exit(AppState::Combat)
+            .with_system(despawn_combat_menu)
+            .with_system(despawn_submenu)
+            .with_system(clear_target_selection)
         )
         .add_system_set(
           SystemSet::on_update(AppState::Combat)
             .with_system(update_enemy_health_text)
+            .with_system(update_party_health_text)
+            .with_system(update_combat_log_text)
+            .with_system(highlight_target)
+            .with_system(close_submenu)
+            .with_system(lock_combat_menu)
+        )
+        .add_system_set(
+          SystemSet::new()
+            .with_run_criteria(player_turn)
             .with_system(attack_button_system)
+            .with_system(run_button_system)
+            .with_system(skills_button_system)
+            .with_system(skill_button_system)
+            .with_system(items_button_system)
+            .with_system(item_button_system)
+            .with_system(submenu_back_button_system)
+            .with_system(target_selection_system)
         );
     }
 }
@@ -40,7 +134,7 @@ fn build_combat_menu(
     ass: ResMut<AssetServer>,
     mut clear_color: ResMut<ClearColor>,
 ) {
-    let mut font = ass.load("fonts/BeBasNeue-Regular.ttf");
+    let font = ass.load(FONT);
     clear_color.0 = Color::BLACK;
     commands
         .spawn(NodeBundle {
@@ -57,60 +151,66 @@ fn build_combat_menu(
             background_color: bevy::prelude::BackgroundColor(Color::BLACK),
             visibility: Visibility {
                 is_visible: true,
-                ..Visibility::default()
             },
             ..NodeBundle::default()
         })
         .insert(CombatMenu)
         .with_children(|parent| {
-            parent.spawn(
-              TextBundle::from_section(
-                "Enemy Health:",
-                TextStyle {
-                  font: font.clone(),
-                  font_size: 30.0,
-                  color: Color::WHITE,
-                }
-              )
-            ).insert(EnemyHealthText);
+            spawn_text(parent, font.clone(), "Enemy Health:", FONT_SIZE).insert(EnemyHealthText);
 
-            // use MenuItem::*;
-            spawn_button(parent, font.clone(), "Attack");
-            spawn_button(parent, font.clone(), "Run");
+            spawn_text(parent, font.clone(), "", FONT_SIZE).insert(PartyHealthText);
+            spawn_text(parent, font.clone(), "", FONT_SIZE).insert(CombatLogText);
+
+            spawn_button(parent, font.clone(), "Attack", COMMAND_BUTTON).insert(AttackButton);
+            spawn_button(parent, font.clone(), "Skills", COMMAND_BUTTON).insert(SkillsButton);
+            spawn_button(parent, font.clone(), "Items", COMMAND_BUTTON).insert(ItemsButton);
+            spawn_button(parent, font.clone(), "Run", COMMAND_BUTTON).insert(RunButton);
         });
 }
 
-fn spawn_button(parent: &mut ChildBuilder, font: Handle<Font>, text: &str) {
-    let mut button = parent
-        .spawn(ButtonBundle {
+fn skill_label(skill: &str, cost: isize) -> String {
+    format!("{} ({} MP)", skill, cost)
+}
+
+fn item_label(item: &str, count: u32) -> String {
+    format!("{} x{}", item, count)
+}
+
+/// Replaces any open submenu with a new list of buttons above the command menu.
+fn spawn_submenu(
+    commands: &mut Commands,
+    menu_query: &Query<Entity, With<CommandSubmenu>>,
+    member: Entity,
+    font: Handle<Font>,
+    spawn_entries: impl FnOnce(&mut ChildBuilder, Handle<Font>)
+) {
+    for menu in menu_query.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+
+    commands
+        .spawn(NodeBundle {
             style: Style {
                 size: Size {
-                    width: Val::Percent(10.),
-                    height: Val::Px(30.),
+                    width: Val::Percent(100.0),
+                    height: Val::Auto,
+                },
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Percent(20.0),
+                    ..default()
                 },
                 flex_direction: FlexDirection::ColumnReverse,
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::SpaceEvenly,
+                align_items: AlignItems::FlexEnd,
                 ..Style::default()
-              },
-              background_color: bevy::prelude::BackgroundColor(Color::GRAY),
-            ..ButtonBundle::default()
+            },
+            ..NodeBundle::default()
+        })
+        .insert(CommandSubmenu { member })
+        .with_children(|parent| {
+            spawn_button(parent, font.clone(), "Back", COMMAND_BUTTON).insert(SubmenuBackButton);
+            spawn_entries(parent, font);
         });
-    if text == "Attack" {
-        button.insert(AttackButton);
-    }
-    button.with_children(|parent| {
-      parent.spawn(
-        TextBundle::from_section(
-          text.to_string(),
-          TextStyle {
-            font: font,
-            font_size: 30.0,
-            color: Color::WHITE,
-          }
-        )
-      );
-    });
 }
 
 fn despawn_combat_menu(
@@ -122,34 +222,147 @@ fn despawn_combat_menu(
   }
 }
 
+fn despawn_submenu(
+  mut commands: Commands,
+  menu_query: Query<Entity, With<CommandSubmenu>>
+) {
+  for entity in menu_query.iter() {
+    commands.entity(entity).despawn_recursive();
+  }
+}
+
+fn clear_target_selection(mut selection: ResMut<TargetSelection>) {
+  *selection = TargetSelection::default();
+}
+
 fn update_enemy_health_text(
   mut text_query: Query<&mut Text, With<EnemyHealthText>>,
-  mut enemy_query: Query<&mut CombatStats, With<Enemy>>,
+  enemy_query: Query<(Entity, &Name, &CombatStats, &StatusEffects), With<Enemy>>,
+  selection: Res<TargetSelection>,
+) {
+  let health = enemy_query
+    .iter()
+    .map(|(enemy, name, combat_stats, effects)| {
+      let marker = if selection.active && selection.selected == Some(enemy) { "> " } else { "" };
+      format!("{}{}: {}/{} {}", marker, name, combat_stats.health, combat_stats.max_health, effects.icons())
+    })
+    .collect::<Vec<_>>()
+    .join("   ");
+
+  for mut text in &mut text_query {
+      text.sections[0].value = health.clone();
+  }
+}
+
+/// Only lets the command buttons run in a fight on a party member's turn, so clicking them
+/// while an enemy acts or the last enemy is dying does nothing.
+fn player_turn(
+    state: Res<State<AppState>>,
+    turn_order: Res<TurnOrder>,
+    party: Res<Party>,
+    pending_victory: Res<PendingVictory>
+) -> ShouldRun {
+    if *state.current() == AppState::Combat
+        && !pending_victory.0
+        && acting_member(&turn_order, &party).is_some()
+    {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn update_party_health_text(
+  mut text_query: Query<&mut Text, With<PartyHealthText>>,
+  member_query: Query<(&Name, &CombatStats, &StatusEffects)>,
+  party: Res<Party>,
+  turn_order: Res<TurnOrder>,
 ) {
-  let combat_stats = enemy_query.single_mut();
+  let acting = acting_member(&turn_order, &party);
+  let health = party
+    .members
+    .iter()
+    .filter_map(|&member| {
+      let (name, combat_stats, effects) = member_query.get(member).ok()?;
+      let marker = if acting == Some(member) { "> " } else { "" };
+      Some(format!(
+        "{}{}: {}/{} MP {}/{} {}",
+        marker,
+        name,
+        combat_stats.health,
+        combat_stats.max_health,
+        combat_stats.mana,
+        combat_stats.max_mana,
+        effects.icons()
+      ))
+    })
+    .collect::<Vec<_>>()
+    .join("   ");
+
   for mut text in &mut text_query {
-      text.sections[0].value = format!("Enemy Health: {}", combat_stats.health);
+      text.sections[0].value = health.clone();
   }
 }
 
+fn update_combat_log_text(
+  mut damage_result: EventReader<DamageResult>,
+  mut item_used: EventReader<ItemUsedEvent>,
+  mut text_query: Query<&mut Text, With<CombatLogText>>,
+  name_query: Query<&Name>,
+) {
+  for event in item_used.iter() {
+    let target = name_query.get(event.target).map_or("?".to_string(), |name| name.to_string());
+    for mut text in &mut text_query {
+      text.sections[0].value = format!("{}: {} {}", event.item, target, event.outcome);
+    }
+  }
+
+  for result in damage_result.iter() {
+    let target = name_query.get(result.target).map_or("?".to_string(), |name| name.to_string());
+    for mut text in &mut text_query {
+      let element = match result.element {
+        Element::Physical => String::new(),
+        element => format!(" {:?}", element)
+      };
+      let feedback = match result.affinity {
+        Affinity::Weak => "Weak! ",
+        Affinity::Resist => "Resist. ",
+        Affinity::Immune => "Immune. ",
+        Affinity::Absorb => "Absorb! ",
+        Affinity::Normal => "",
+      };
+      text.sections[0].value = if result.heal {
+        format!("{}{} recovers {} health", feedback, target, result.amount)
+      } else if result.critical {
+        format!("{}Critical! {} takes {}{} damage", feedback, target, result.amount, element)
+      } else {
+        format!("{}{} takes {}{} damage", feedback, target, result.amount, element)
+      };
+    }
+  }
+}
+
+#[allow(clippy::type_complexity)]
 fn attack_button_system(
     mut interaction_query: Query<
         (&Interaction, &Children),
         (Changed<Interaction>, With<AttackButton>),
     >,
     mut text_query: Query<&mut Text>,
-    mut fight_event: EventWriter<FightEvent>,
-    enemy_query: Query<Entity, With<Enemy>>,
-    player_query: Query<&CombatStats, With<Player>>
+    enemy_query: Query<(Entity, &CombatStats, &Transform), With<Enemy>>,
+    mut selection: ResMut<TargetSelection>
 ) {
     for (interaction, children) in &mut interaction_query {
         let mut text = text_query.get_mut(children[0]).unwrap();
-        let target = enemy_query.iter().next().unwrap();
-        let player_stats = player_query.single();
         match *interaction {
             Interaction::Clicked => {
-                text.sections[0].value = "Attacked!".to_string();
-                fight_event.send(FightEvent { target, damage_amount: player_stats.attack });
+                text.sections[0].value = "Target?".to_string();
+                *selection = TargetSelection {
+                    active: true,
+                    selected: living_targets(&enemy_query).first().copied(),
+                    action: PendingAction::Attack,
+                    side: TargetSide::Enemies
+                };
             }
             Interaction::Hovered => {
             }
@@ -158,4 +371,436 @@ fn attack_button_system(
             }
         }
     }
-}
\ No newline at end of file
+}
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn run_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &Children),
+        (Changed<Interaction>, With<RunButton>),
+    >,
+    mut text_query: Query<&mut Text>,
+    enemy_query: Query<&CombatStats, With<Enemy>>,
+    member_query: Query<&CombatStats, Without<Enemy>>,
+    party: Res<Party>,
+    encounter: Option<Res<Encounter>>,
+    mut rng: ResMut<GameRng>,
+    mut turn_order: ResMut<TurnOrder>,
+    mut selection: ResMut<TargetSelection>,
+    mut state: ResMut<State<AppState>>
+) {
+    for (interaction, children) in &mut interaction_query {
+        let mut text = text_query.get_mut(children[0]).unwrap();
+        match *interaction {
+            Interaction::Clicked => {
+                let Some(member) = acting_member(&turn_order, &party) else {
+                    continue;
+                };
+                let member_stats = member_query.get(member).unwrap();
+                selection.active = false;
+                if encounter.as_ref().is_some_and(|encounter| !encounter.escapable) {
+                    text.sections[0].value = "No escape!".to_string();
+                    continue;
+                }
+                if rng.gen::<f32>() < escape_chance(member_stats, enemy_query.iter()) {
+                    state.set(AppState::OverWorld).unwrap();
+                } else {
+                    text.sections[0].value = "Failed!".to_string();
+                    turn_order.end_turn();
+                }
+            }
+            Interaction::Hovered => {
+            }
+            Interaction::None => {
+              text.sections[0].value = "Run".to_string();
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn skills_button_system(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<SkillsButton>)>,
+    menu_query: Query<Entity, With<CommandSubmenu>>,
+    skills_query: Query<&KnownSkills>,
+    skill_book: Res<SkillBook>,
+    party: Res<Party>,
+    turn_order: Res<TurnOrder>,
+    mut selection: ResMut<TargetSelection>,
+    ass: Res<AssetServer>
+) {
+    for interaction in &interaction_query {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        let Some(member) = acting_member(&turn_order, &party) else {
+            continue;
+        };
+        selection.active = false;
+
+        let skills = skills_query.get(member).map(|known| known.0.clone()).unwrap_or_default();
+        spawn_submenu(&mut commands, &menu_query, member, ass.load(FONT), |parent, font| {
+            for skill in skills.iter().filter_map(|name| skill_book.get(name)) {
+                spawn_button(parent, font.clone(), &skill_label(&skill.name, skill.cost), COMMAND_BUTTON)
+                    .insert(SkillButton { skill: skill.name.clone(), cost: skill.cost });
+            }
+        });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn items_button_system(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ItemsButton>)>,
+    menu_query: Query<Entity, With<CommandSubmenu>>,
+    inventory_query: Query<&Inventory, With<Player>>,
+    party: Res<Party>,
+    turn_order: Res<TurnOrder>,
+    mut selection: ResMut<TargetSelection>,
+    ass: Res<AssetServer>
+) {
+    for interaction in &interaction_query {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        let Some(member) = acting_member(&turn_order, &party) else {
+            continue;
+        };
+        selection.active = false;
+
+        let stacks = inventory_query.get_single().map(|inventory| inventory.stacks.clone()).unwrap_or_default();
+        spawn_submenu(&mut commands, &menu_query, member, ass.load(FONT), |parent, font| {
+            for stack in &stacks {
+                spawn_button(parent, font.clone(), &item_label(&stack.item, stack.count), COMMAND_BUTTON)
+                    .insert(ItemButton { item: stack.item.clone() });
+            }
+        });
+    }
+}
+
+/// Items are only used on party members, so picking one always starts an ally selection.
+fn item_button_system(
+    interaction_query: Query<(&Interaction, &ItemButton), Changed<Interaction>>,
+    party: Res<Party>,
+    turn_order: Res<TurnOrder>,
+    mut selection: ResMut<TargetSelection>
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        let Some(member) = acting_member(&turn_order, &party) else {
+            continue;
+        };
+
+        *selection = TargetSelection {
+            active: true,
+            selected: Some(member),
+            action: PendingAction::Item(button.item.clone()),
+            side: TargetSide::Party
+        };
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn skill_button_system(
+    interaction_query: Query<(&Interaction, &Children, &SkillButton), Changed<Interaction>>,
+    mut text_query: Query<&mut Text>,
+    enemy_query: Query<(Entity, &CombatStats, &Transform), With<Enemy>>,
+    mut member_query: Query<&mut CombatStats, Without<Enemy>>,
+    skill_book: Res<SkillBook>,
+    effective_query: Query<&EffectiveStats>,
+    mut item_use: ItemUse,
+    party: Res<Party>,
+    mut turn_order: ResMut<TurnOrder>,
+    mut selection: ResMut<TargetSelection>,
+    mut fight_event: EventWriter<FightEvent>
+) {
+    for (interaction, children, button) in &interaction_query {
+        let mut text = text_query.get_mut(children[0]).unwrap();
+        match *interaction {
+            Interaction::Clicked => {
+                let Some(member) = acting_member(&turn_order, &party) else {
+                    continue;
+                };
+                let Some(skill) = skill_book.get(&button.skill) else {
+                    continue;
+                };
+                if member_query.get(member).unwrap().mana < skill.cost {
+                    text.sections[0].value = "Not enough MP".to_string();
+                    continue;
+                }
+
+                let action = PendingAction::Skill(skill.name.clone());
+                match skill.target {
+                    SkillTarget::Single => {
+                        *selection = TargetSelection {
+                            active: true,
+                            selected: living_targets(&enemy_query).first().copied(),
+                            action,
+                            side: TargetSide::Enemies
+                        };
+                    }
+                    SkillTarget::Ally => {
+                        *selection = TargetSelection {
+                            active: true,
+                            selected: Some(member),
+                            action,
+                            side: TargetSide::Party
+                        };
+                    }
+                    SkillTarget::All => {
+                        let targets = living_targets(&enemy_query);
+                        perform_action(member, &targets, &action, &skill_book, &mut member_query, &effective_query, &mut item_use, &mut fight_event, &mut turn_order);
+                    }
+                    SkillTarget::User => {
+                        perform_action(member, &[member], &action, &skill_book, &mut member_query, &effective_query, &mut item_use, &mut fight_event, &mut turn_order);
+                    }
+                }
+            }
+            Interaction::Hovered => {
+            }
+            Interaction::None => {
+              text.sections[0].value = skill_label(&button.skill, button.cost);
+            }
+        }
+    }
+}
+
+fn submenu_back_button_system(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<SubmenuBackButton>)>,
+    menu_query: Query<Entity, With<CommandSubmenu>>
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Clicked {
+            for menu in &menu_query {
+                commands.entity(menu).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Closes the skill list once its member's turn is over or a target is being picked.
+fn close_submenu(
+    mut commands: Commands,
+    menu_query: Query<(Entity, &CommandSubmenu)>,
+    party: Res<Party>,
+    turn_order: Res<TurnOrder>,
+    selection: Res<TargetSelection>
+) {
+    let acting = acting_member(&turn_order, &party);
+    for (entity, menu) in &menu_query {
+        if selection.active || acting != Some(menu.member) {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Carries out a chosen command and hands the turn on. Skills are paid for and items used up here.
+#[allow(clippy::too_many_arguments)]
+fn perform_action(
+    member: Entity,
+    targets: &[Entity],
+    action: &PendingAction,
+    skill_book: &SkillBook,
+    member_query: &mut Query<&mut CombatStats, Without<Enemy>>,
+    effective_query: &Query<&EffectiveStats>,
+    item_use: &mut ItemUse,
+    fight_event: &mut EventWriter<FightEvent>,
+    turn_order: &mut TurnOrder
+) {
+    match action {
+        PendingAction::Attack => {
+            let attack = effective_query.get(member).unwrap().attack;
+            for &target in targets {
+                fight_event.send(FightEvent::attack(member, target, attack));
+            }
+        }
+        PendingAction::Skill(name) => {
+            let skill = skill_book.get(name).expect("Using a skill missing from the skill book!");
+            let mut member_stats = member_query.get_mut(member).unwrap();
+            member_stats.mana -= skill.cost;
+            let amount = match skill.effect {
+                FightEffect::Damage => effective_query.get(member).unwrap().attack + skill.power,
+                FightEffect::Heal | FightEffect::Direct | FightEffect::Status => skill.power
+            };
+            for &target in targets {
+                fight_event.send(FightEvent {
+                    attacker: member,
+                    target,
+                    damage_amount: amount,
+                    element: skill.element,
+                    effect: skill.effect,
+                    status: skill.status
+                });
+            }
+        }
+        PendingAction::Item(item) => {
+            for &target in targets {
+                let mut target_stats = member_query.get_mut(target).unwrap();
+                if !item_use.use_item(item, target, &mut target_stats) {
+                    // Nothing happened, so the member still gets to act.
+                    return;
+                }
+            }
+        }
+    }
+    turn_order.end_turn();
+}
+
+/// The party member whose command is being chosen, if it is the party's turn.
+fn acting_member(turn_order: &TurnOrder, party: &Party) -> Option<Entity> {
+    turn_order.active().filter(|&active| party.contains(active))
+}
+
+/// Living enemies ordered left to right, the order the selection cycles through.
+fn living_targets(
+    enemy_query: &Query<(Entity, &CombatStats, &Transform), With<Enemy>>
+) -> Vec<Entity> {
+    let mut targets: Vec<(Entity, f32)> = enemy_query
+        .iter()
+        .filter(|(_, stats, _)| stats.health > 0)
+        .map(|(enemy, _, transform)| (enemy, transform.translation.x))
+        .collect();
+    targets.sort_by(|a, b| a.1.total_cmp(&b.1));
+    targets.into_iter().map(|(enemy, _)| enemy).collect()
+}
+
+fn cursor_world_position(
+    windows: &Windows,
+    camera_query: &Query<(&Camera, &GlobalTransform)>
+) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    let (camera, camera_transform) = camera_query.iter().next()?;
+
+    // Viewport rects are measured from the top of the window, cursor positions from the bottom.
+    let (min, max) = camera.logical_viewport_rect()?;
+    let viewport_origin = Vec2::new(min.x, window.height() - max.y);
+    let ndc = ((cursor - viewport_origin) / (max - min)) * 2.0 - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+    Some(ndc_to_world.project_point3(ndc.extend(-1.0)).truncate())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn target_selection_system(
+    actions: Res<Input<Action>>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+    enemy_query: Query<(Entity, &CombatStats, &Transform), With<Enemy>>,
+    battler_query: Query<(&PartyBattler, &Transform)>,
+    mut member_query: Query<&mut CombatStats, Without<Enemy>>,
+    skill_book: Res<SkillBook>,
+    effective_query: Query<&EffectiveStats>,
+    mut item_use: ItemUse,
+    party: Res<Party>,
+    mut selection: ResMut<TargetSelection>,
+    mut turn_order: ResMut<TurnOrder>,
+    mut fight_event: EventWriter<FightEvent>
+) {
+    if !selection.active {
+        return;
+    }
+    let Some(member) = acting_member(&turn_order, &party) else {
+        selection.active = false;
+        return;
+    };
+
+    let targets = match selection.side {
+        TargetSide::Enemies => living_targets(&enemy_query),
+        TargetSide::Party => party
+            .members
+            .iter()
+            .copied()
+            .filter(|&ally| member_query.get(ally).is_ok_and(|stats| stats.health > 0))
+            .collect()
+    };
+    if targets.is_empty() {
+        selection.active = false;
+        return;
+    }
+
+    let current = selection
+        .selected
+        .and_then(|selected| targets.iter().position(|&target| target == selected))
+        .unwrap_or(0);
+    let mut confirmed = false;
+
+    if actions.any_just_pressed([Action::MoveLeft, Action::MoveUp]) {
+        selection.selected = Some(targets[(current + targets.len() - 1) % targets.len()]);
+    } else if actions.any_just_pressed([Action::MoveRight, Action::MoveDown]) {
+        selection.selected = Some(targets[(current + 1) % targets.len()]);
+    } else if actions.just_pressed(Action::Confirm) {
+        selection.selected = Some(targets[current]);
+        confirmed = true;
+    } else if actions.just_pressed(Action::Cancel) {
+        selection.active = false;
+        return;
+    } else if mouse.just_pressed(MouseButton::Left) {
+        if let Some(cursor) = cursor_world_position(&windows, &camera_query) {
+            let bounds = |target| match selection.side {
+                TargetSide::Enemies => enemy_query
+                    .get(target)
+                    .ok()
+                    .map(|(_, _, transform)| (transform.translation.truncate(), ENEMY_SIZE)),
+                TargetSide::Party => battler_query
+                    .iter()
+                    .find(|(battler, _)| battler.member == target)
+                    .map(|(_, transform)| (transform.translation.truncate(), BATTLER_SIZE))
+            };
+            let clicked = targets.iter().copied().find(|&target| {
+                bounds(target).is_some_and(|(position, size)| {
+                    (cursor - position).abs().max_element() <= size / 2.0
+                })
+            });
+            if clicked.is_some() {
+                selection.selected = clicked;
+                confirmed = true;
+            }
+        }
+    }
+
+    if confirmed {
+        let target = selection.selected.unwrap();
+        perform_action(member, &[target], &selection.action, &skill_book, &mut member_query, &effective_query, &mut item_use, &mut fight_event, &mut turn_order);
+        selection.active = false;
+    }
+}
+
+fn highlight_target(
+    selection: Res<TargetSelection>,
+    mut enemy_query: Query<(Entity, &mut TextureAtlasSprite), With<Enemy>>,
+    mut battler_query: Query<(&PartyBattler, &mut TextureAtlasSprite), Without<Enemy>>
+) {
+    let tint = |entity| if selection.active && selection.selected == Some(entity) {
+        SELECTED_TINT
+    } else {
+        Color::WHITE
+    };
+
+    for (enemy, mut sprite) in &mut enemy_query {
+        sprite.color = tint(enemy);
+    }
+    for (battler, mut sprite) in &mut battler_query {
+        sprite.color = tint(battler.member);
+    }
+}
+
+/// Greys the buttons out while they are locked by `player_turn`.
+fn lock_combat_menu(
+    turn_order: Res<TurnOrder>,
+    party: Res<Party>,
+    mut button_query: Query<&mut BackgroundColor, With<Button>>
+) {
+    let color = if acting_member(&turn_order, &party).is_some() {
+        Color::GRAY
+    } else {
+        Color::DARK_GRAY
+    };
+
+    for mut background_color in &mut button_query {
+        background_color.0 = color;
+    }
+}
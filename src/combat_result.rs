@@ -0,0 +1,244 @@
+use bevy::{prelude::*, ecs::system::EntityCommands};
+use bevy_ecs_ldtk::prelude::Respawn;
+use bevy_inspector_egui::Inspectable;
+
+use crate::{AppState, FONT, combat_stats::CombatStats, experience::Experience, map::Map, party::Party, player::Player};
+use crate::equipment::{EquipmentBook, SpareEquipment};
+use crate::items::{Inventory, ItemBook};
+use crate::loot::{store_item, LootTables, Wallet};
+use crate::rng::GameRng;
+use crate::save::{latest_slot, read_save, slot_path, LoadSave};
+use crate::ui::{spawn_button, spawn_text, ButtonLayout, FONT_SIZE};
+
+const RESULT_BUTTON: ButtonLayout = ButtonLayout {
+  width: Val::Percent(10.),
+  height: Val::Px(30.),
+  margin: Val::Px(10.),
+  font_size: FONT_SIZE
+};
+
+pub struct CombatResultPlugin;
+
+/// What the player gets to see on the victory screen.
+#[derive(Resource, Default)]
+pub struct VictorySummary {
+  pub defeated: Vec<String>,
+  pub experience: u32,
+  /// Member name and the level they reached.
+  pub levels_reached: Vec<(String, u32)>,
+  /// One entry per defeated enemy that drops loot, rolled when the screen opens.
+  pub loot_tables: Vec<String>,
+  pub gold: u32,
+  pub items: Vec<String>
+}
+
+#[derive(Component, Inspectable)]
+pub struct ResultScreen;
+
+#[derive(Component, Inspectable)]
+pub struct ContinueButton;
+
+/// Loads the most recent save, or respawns the party on the map if there is none.
+#[derive(Component, Inspectable)]
+pub struct RetryButton;
+
+impl Plugin for CombatResultPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_system_set(
+          SystemSet::on_enter(AppState::Victory)
+            .with_system(award_experience)
+            .with_system(award_loot)
+            .with_system(build_victory_screen.after(award_experience).after(award_loot))
+        )
+        .add_system_set(
+          SystemSet::on_update(AppState::Victory).with_system(continue_button_system)
+        )
+        .add_system_set(
+          SystemSet::on_exit(AppState::Victory)
+            .with_system(despawn_result_screen)
+            .with_system(clear_victory_summary)
+        )
+        .add_system_set(
+          SystemSet::on_enter(AppState::GameOver).with_system(build_game_over_screen)
+        )
+        .add_system_set(
+          SystemSet::on_update(AppState::GameOver).with_system(retry_button_system)
+        )
+        .add_system_set(
+          SystemSet::on_exit(AppState::GameOver).with_system(despawn_result_screen)
+        );
+    }
+}
+
+fn build_victory_screen(
+    mut commands: Commands,
+    ass: Res<AssetServer>,
+    summary: Res<VictorySummary>
+) {
+    let font = ass.load(FONT);
+    let mut lines = vec!["Victory!".to_string()];
+    lines.extend(summary.defeated.iter().map(|name| format!("Defeated {}", name)));
+    lines.push(format!("Gained {} XP", summary.experience));
+    if summary.gold > 0 {
+      lines.push(format!("Found {} gold", summary.gold));
+    }
+    lines.extend(summary.items.iter().map(|item| format!("Found {}", item)));
+    lines.extend(
+      summary
+        .levels_reached
+        .iter()
+        .map(|(name, level)| format!("Level up! {} reached level {}", name, level))
+    );
+
+    spawn_result_screen(&mut commands, font.clone(), &lines)
+        .with_children(|parent| {
+            spawn_button(parent, font, "Continue", RESULT_BUTTON).insert(ContinueButton);
+        });
+}
+
+fn award_experience(
+    mut summary: ResMut<VictorySummary>,
+    party: Res<Party>,
+    mut member_query: Query<(&Name, &mut Experience, &mut CombatStats)>
+) {
+    let experience_gained = summary.experience;
+    for &member in &party.members {
+        let Ok((name, mut experience, mut stats)) = member_query.get_mut(member) else {
+            continue;
+        };
+        if stats.health == 0 {
+            continue;
+        }
+
+        for level in experience.gain(experience_gained, &mut stats) {
+            summary.levels_reached.push((name.to_string(), level));
+        }
+    }
+}
+
+fn award_loot(
+    mut summary: ResMut<VictorySummary>,
+    tables: Res<LootTables>,
+    item_book: Res<ItemBook>,
+    equipment_book: Res<EquipmentBook>,
+    mut rng: ResMut<GameRng>,
+    mut player_query: Query<(&mut Wallet, &mut Inventory, &mut SpareEquipment), With<Player>>
+) {
+    let Ok((mut wallet, mut inventory, mut spare)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    for name in summary.loot_tables.clone() {
+        let Some(table) = tables.0.get(&name) else {
+            warn!("Unknown loot table {}", name);
+            continue;
+        };
+
+        let (gold, item) = table.roll(&mut *rng);
+        wallet.gold += gold;
+        summary.gold += gold;
+        let Some(item) = item else {
+            continue;
+        };
+        if !store_item(&item, &item_book, &equipment_book, &mut inventory, &mut spare) {
+            warn!("Loot table {} drops unknown item {}", name, item);
+            continue;
+        }
+        summary.items.push(item);
+    }
+}
+
+fn build_game_over_screen(mut commands: Commands, ass: Res<AssetServer>) {
+    let font = ass.load(FONT);
+
+    let label = if latest_slot().is_some() { "Load Save" } else { "Respawn" };
+    spawn_result_screen(&mut commands, font.clone(), &["Game Over".to_string()])
+        .with_children(|parent| {
+            spawn_button(parent, font, label, RESULT_BUTTON).insert(RetryButton);
+        });
+}
+
+fn spawn_result_screen<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    font: Handle<Font>,
+    lines: &[String]
+) -> EntityCommands<'w, 's, 'a> {
+    let mut screen = commands.spawn(NodeBundle {
+        style: Style {
+            size: Size {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+            },
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..Style::default()
+        },
+        background_color: BackgroundColor(Color::BLACK),
+        ..NodeBundle::default()
+    });
+    screen.insert(ResultScreen).with_children(|parent| {
+        for line in lines {
+            spawn_text(parent, font.clone(), line, FONT_SIZE);
+        }
+    });
+    screen
+}
+
+fn continue_button_system(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ContinueButton>)>,
+    mut state: ResMut<State<AppState>>
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Clicked {
+            state.set(AppState::OverWorld).unwrap();
+        }
+    }
+}
+
+fn retry_button_system(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<RetryButton>)>,
+    party: Res<Party>,
+    mut member_query: Query<&mut CombatStats>,
+    map_query: Query<Entity, With<Map>>,
+    mut load_event: EventWriter<LoadSave>,
+    mut state: ResMut<State<AppState>>
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Clicked {
+            state.set(AppState::OverWorld).unwrap();
+
+            if let Some(slot) = latest_slot() {
+                match slot_path(slot).and_then(|path| read_save(&path)) {
+                    Ok(data) => {
+                        load_event.send(LoadSave { slot, data });
+                        continue;
+                    }
+                    Err(error) => warn!("Could not load slot {}, respawning instead: {}", slot, error),
+                }
+            }
+
+            for &member in &party.members {
+                if let Ok(mut stats) = member_query.get_mut(member) {
+                    stats.health = stats.max_health;
+                }
+            }
+            commands.entity(map_query.single()).insert(Respawn);
+        }
+    }
+}
+
+fn despawn_result_screen(
+  mut commands: Commands,
+  screen_query: Query<Entity, With<ResultScreen>>
+) {
+  for entity in screen_query.iter() {
+    commands.entity(entity).despawn_recursive();
+  }
+}
+
+fn clear_victory_summary(mut commands: Commands) {
+  commands.remove_resource::<VictorySummary>();
+}
@@ -1,10 +1,53 @@
 use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Inspectable)]
+#[derive(Component, Inspectable, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct CombatStats {
   pub health: isize,
   pub max_health: isize,
   pub attack: isize,
-  pub defense: isize
+  pub defense: isize,
+  pub mana: isize,
+  pub max_mana: isize
+}
+
+impl CombatStats {
+    /// Restores up to `amount` health without going over `max_health`, returning how much was restored.
+    pub fn heal(&mut self, amount: isize) -> isize {
+      let healed = amount.min(self.max_health - self.health).max(0);
+      self.health += healed;
+      healed
+    }
+
+    /// Restores up to `amount` mana without going over `max_mana`, returning how much was restored.
+    pub fn restore_mana(&mut self, amount: isize) -> isize {
+      let restored = amount.min(self.max_mana - self.mana).max(0);
+      self.mana += restored;
+      restored
+    }
+}
+
+/// Attack and defense after equipment and statuses are layered on the base `CombatStats`.
+/// Kept up to date by the equipment plugin; damage is always worked out from these.
+#[derive(Component, Inspectable, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EffectiveStats {
+  pub attack: isize,
+  pub defense: isize,
+  /// The part of `attack` that comes from statuses such as Focus.
+  pub attack_bonus: isize,
+  /// The part of `defense` that comes from statuses such as Guard.
+  pub defense_bonus: isize
+}
+
+impl EffectiveStats {
+    /// Effective stats with nothing layered on yet.
+    pub fn base(stats: &CombatStats) -> Self {
+      EffectiveStats {
+        attack: stats.attack,
+        defense: stats.defense,
+        attack_bonus: 0,
+        defense_bonus: 0
+      }
+    }
 }
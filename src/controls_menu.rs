@@ -0,0 +1,237 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+
+use crate::{AppState, FONT, notice::NoticeEvent};
+use crate::actions::{Action, ActionBindings, Binding};
+use crate::ui::{spawn_button, spawn_text, ButtonLayout};
+
+/// Smaller than the other menus so every action fits on screen.
+const CONTROLS_FONT_SIZE: f32 = 20.0;
+const CONTROL_BUTTON: ButtonLayout = ButtonLayout {
+  width: Val::Percent(50.),
+  height: Val::Px(22.),
+  margin: Val::Px(2.),
+  font_size: CONTROLS_FONT_SIZE
+};
+
+pub struct ControlsMenuPlugin;
+
+#[derive(Component, Inspectable)]
+pub struct ControlsScreen;
+
+#[derive(Component, Inspectable)]
+pub struct ControlsInfoText;
+
+/// Waits for a new key or button for `action` once clicked.
+#[derive(Component)]
+pub struct RebindButton {
+  pub action: Action
+}
+
+#[derive(Component, Inspectable)]
+pub struct ResetControlsButton;
+
+#[derive(Component, Inspectable)]
+pub struct CloseControlsButton;
+
+/// The action waiting for its new binding.
+#[derive(Resource, Default)]
+pub struct PendingRebind(pub Option<Action>);
+
+impl Plugin for ControlsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<PendingRebind>()
+        .add_system_set(
+          SystemSet::on_update(AppState::OverWorld).with_system(open_controls_menu)
+        )
+        .add_system_set(
+          SystemSet::on_update(AppState::Controls)
+            .with_system(close_controls_menu)
+            .with_system(refresh_controls_screen)
+            .with_system(rebind_button_system)
+            .with_system(reset_controls_button_system)
+            .with_system(capture_rebind)
+            .with_system(update_controls_info_text)
+        )
+        .add_system_set(
+          SystemSet::on_exit(AppState::Controls)
+            .with_system(despawn_controls_screen)
+            .with_system(clear_pending_rebind)
+        );
+    }
+}
+
+fn open_controls_menu(
+  mut actions: ResMut<Input<Action>>,
+  mut state: ResMut<State<AppState>>
+) {
+  if actions.just_pressed(Action::Controls) && state.push(AppState::Controls).is_ok() {
+    actions.clear()
+  }
+}
+
+/// `Cancel` closes the screen, unless it is waiting for a binding; then it only stops waiting.
+fn close_controls_menu(
+  mut actions: ResMut<Input<Action>>,
+  pending: Res<PendingRebind>,
+  interaction_query: Query<&Interaction, (Changed<Interaction>, With<CloseControlsButton>)>,
+  mut state: ResMut<State<AppState>>
+) {
+  let clicked = interaction_query.iter().any(|interaction| *interaction == Interaction::Clicked);
+  let cancelled = pending.0.is_none() && actions.any_just_pressed([Action::Controls, Action::Cancel]);
+  if clicked || cancelled {
+    state.pop().unwrap();
+
+    actions.clear()
+  }
+}
+
+/// Builds the screen on the first frame and rebuilds it whenever a binding changes.
+fn refresh_controls_screen(
+    mut commands: Commands,
+    ass: Res<AssetServer>,
+    bindings: Res<ActionBindings>,
+    screen_query: Query<Entity, With<ControlsScreen>>
+) {
+    if !screen_query.is_empty() && !bindings.is_changed() {
+        return;
+    }
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let font = ass.load(FONT);
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                },
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Style::default()
+            },
+            background_color: BackgroundColor(Color::rgba(0.0, 0.0, 0.0, 0.85)),
+            ..NodeBundle::default()
+        })
+        .insert(ControlsScreen)
+        .with_children(|parent| {
+            spawn_text(parent, font.clone(), "Controls", CONTROLS_FONT_SIZE);
+
+            for (&action, action_bindings) in &bindings.0 {
+                let labels = action_bindings.iter().map(Binding::label).collect::<Vec<_>>().join(", ");
+                spawn_button(parent, font.clone(), &format!("{:?}: {}", action, labels), CONTROL_BUTTON)
+                    .insert(RebindButton { action });
+            }
+
+            spawn_text(parent, font.clone(), "", CONTROLS_FONT_SIZE).insert(ControlsInfoText);
+            spawn_button(parent, font.clone(), "Reset to defaults", CONTROL_BUTTON).insert(ResetControlsButton);
+            spawn_button(parent, font, "Close", CONTROL_BUTTON).insert(CloseControlsButton);
+        });
+}
+
+/// Clears the input that clicked the button, so it is not taken as the new binding.
+fn rebind_button_system(
+    interaction_query: Query<(&Interaction, &RebindButton), Changed<Interaction>>,
+    mut pending: ResMut<PendingRebind>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut buttons: ResMut<Input<GamepadButton>>
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction == Interaction::Clicked {
+            pending.0 = Some(button.action);
+            keyboard.clear();
+            buttons.clear();
+        }
+    }
+}
+
+fn reset_controls_button_system(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ResetControlsButton>)>,
+    mut pending: ResMut<PendingRebind>,
+    mut bindings: ResMut<ActionBindings>
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Clicked {
+            pending.0 = None;
+            *bindings = ActionBindings::defaults();
+            bindings.save();
+        }
+    }
+}
+
+/// Takes the next key or gamepad button as the pending action's binding. `Escape` gives up
+/// instead, so it cannot be bound this way. An input another action uses is swapped over, or
+/// turned down if there is nothing to swap.
+fn capture_rebind(
+    mut pending: ResMut<PendingRebind>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    buttons: Res<Input<GamepadButton>>,
+    mut actions: ResMut<Input<Action>>,
+    mut bindings: ResMut<ActionBindings>,
+    mut notice_event: EventWriter<NoticeEvent>
+) {
+    let Some(action) = pending.0 else {
+        return;
+    };
+    if keyboard.just_pressed(KeyCode::Escape) {
+        pending.0 = None;
+        keyboard.clear();
+        actions.clear();
+        return;
+    }
+
+    let binding = keyboard
+        .get_just_pressed()
+        .next()
+        .map(|&key| Binding::Key(key))
+        .or_else(|| buttons.get_just_pressed().next().map(|button| Binding::Button(button.button_type)));
+    let Some(binding) = binding else {
+        return;
+    };
+
+    pending.0 = None;
+    actions.clear();
+    match bindings.rebind(action, binding) {
+        Ok(swapped) => {
+            if let Some(other) = swapped {
+                notice_event.send(NoticeEvent(format!("{:?} now uses {}; {:?} takes its old binding", action, binding.label(), other)));
+            }
+            bindings.save();
+        }
+        Err(other) => {
+            notice_event.send(NoticeEvent(format!("{} is already used by {:?}", binding.label(), other)));
+        }
+    }
+}
+
+fn update_controls_info_text(
+    pending: Res<PendingRebind>,
+    mut text_query: Query<&mut Text, With<ControlsInfoText>>
+) {
+    let info = match pending.0 {
+        Some(action) => format!("Press a key or button for {:?} (Escape to cancel)", action),
+        None => "Click an action to rebind it".to_string(),
+    };
+    for mut text in &mut text_query {
+        if text.sections[0].value != info {
+            text.sections[0].value = info.clone();
+        }
+    }
+}
+
+fn despawn_controls_screen(
+  mut commands: Commands,
+  screen_query: Query<Entity, With<ControlsScreen>>
+) {
+  for entity in screen_query.iter() {
+    commands.entity(entity).despawn_recursive();
+  }
+}
+
+fn clear_pending_rebind(mut pending: ResMut<PendingRebind>) {
+  pending.0 = None;
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+const MIN_DAMAGE: isize = 1;
+const DAMAGE_VARIANCE: f32 = 0.1;
+const CRITICAL_CHANCE: f32 = 0.05;
+const CRITICAL_MULTIPLIER: f32 = 2.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Element {
+  #[default]
+  Physical,
+  Fire,
+  Ice,
+  Lightning
+}
+
+/// How a combatant takes hits of a given element.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Affinity {
+  Weak,
+  #[default]
+  Normal,
+  Resist,
+  Immune,
+  /// The hit heals instead of hurting.
+  Absorb
+}
+
+impl Affinity {
+    /// Scale applied to the size of the hit; absorbed hits keep their size and heal.
+    pub fn multiplier(&self) -> f32 {
+      match self {
+        Affinity::Weak => 2.0,
+        Affinity::Normal | Affinity::Absorb => 1.0,
+        Affinity::Resist => 0.5,
+        Affinity::Immune => 0.0,
+      }
+    }
+}
+
+/// Per-element affinities; elements not listed are `Normal`.
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Affinities(pub HashMap<Element, Affinity>);
+
+impl Affinities {
+    pub fn get(&self, element: Element) -> Affinity {
+      self.0.get(&element).copied().unwrap_or_default()
+    }
+}
+
+/// Sent once a `FightEvent` has gone through the `DamagePipeline` and been applied.
+/// For heals `amount` is the health restored.
+#[derive(Clone, Debug)]
+pub struct DamageResult {
+  pub attacker: Entity,
+  pub target: Entity,
+  pub amount: isize,
+  pub element: Element,
+  pub affinity: Affinity,
+  pub heal: bool,
+  pub critical: bool,
+  pub remaining_health: isize
+}
+
+/// Working state for one hit as it moves through the pipeline stages.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DamageContext {
+  pub attack: isize,
+  pub defense: isize,
+  pub attack_bonus: isize,
+  pub defense_bonus: isize,
+  pub affinity: Affinity,
+  pub amount: f32,
+  pub critical: bool
+}
+
+impl DamageContext {
+    pub fn new(attack: isize, defense: isize) -> Self {
+      DamageContext {
+        attack,
+        defense,
+        attack_bonus: 0,
+        defense_bonus: 0,
+        affinity: Affinity::Normal,
+        amount: 0.0,
+        critical: false
+      }
+    }
+
+    pub fn final_amount(&self) -> isize {
+      self.amount.round() as isize
+    }
+}
+
+pub trait DamageStage: Send + Sync {
+    fn apply(&self, context: &mut DamageContext, rng: &mut dyn RngCore);
+}
+
+/// Starts the hit at the attacker's raw attack.
+pub struct BaseDamage;
+
+impl DamageStage for BaseDamage {
+    fn apply(&self, context: &mut DamageContext, _: &mut dyn RngCore) {
+      context.amount = context.attack as f32;
+    }
+}
+
+/// Folds temporary attack and defense bonuses into the hit.
+pub struct Buffs;
+
+impl DamageStage for Buffs {
+    fn apply(&self, context: &mut DamageContext, _: &mut dyn RngCore) {
+      context.amount += context.attack_bonus as f32;
+      context.defense += context.defense_bonus;
+    }
+}
+
+/// Scales the hit by a random factor in `1.0 ± spread`.
+pub struct Variance {
+  pub spread: f32
+}
+
+impl DamageStage for Variance {
+    fn apply(&self, context: &mut DamageContext, rng: &mut dyn RngCore) {
+      if self.spread > 0.0 {
+        context.amount *= rng.gen_range(1.0 - self.spread..=1.0 + self.spread);
+      }
+    }
+}
+
+pub struct Critical {
+  pub chance: f32,
+  pub multiplier: f32
+}
+
+impl DamageStage for Critical {
+    fn apply(&self, context: &mut DamageContext, rng: &mut dyn RngCore) {
+      if rng.gen::<f32>() < self.chance {
+        context.critical = true;
+        context.amount *= self.multiplier;
+      }
+    }
+}
+
+/// Applies the target's affinity to the attack's element.
+pub struct Elemental;
+
+impl DamageStage for Elemental {
+    fn apply(&self, context: &mut DamageContext, _: &mut dyn RngCore) {
+      context.amount *= context.affinity.multiplier();
+    }
+}
+
+/// Subtracts defense, never letting a hit drop below `floor` unless the target is immune.
+pub struct DefenseMitigation {
+  pub floor: isize
+}
+
+impl DamageStage for DefenseMitigation {
+    fn apply(&self, context: &mut DamageContext, _: &mut dyn RngCore) {
+      let floor = if context.affinity == Affinity::Immune { 0.0 } else { self.floor as f32 };
+      context.amount = (context.amount - context.defense as f32).max(floor);
+    }
+}
+
+/// Ordered stages every `FightEvent` is run through.
+#[derive(Resource)]
+pub struct DamagePipeline {
+  pub stages: Vec<Box<dyn DamageStage>>
+}
+
+impl Default for DamagePipeline {
+    fn default() -> Self {
+      DamagePipeline {
+        stages: vec![
+          Box::new(BaseDamage),
+          Box::new(Buffs),
+          Box::new(Variance { spread: DAMAGE_VARIANCE }),
+          Box::new(Critical { chance: CRITICAL_CHANCE, multiplier: CRITICAL_MULTIPLIER }),
+          Box::new(Elemental),
+          Box::new(DefenseMitigation { floor: MIN_DAMAGE }),
+        ]
+      }
+    }
+}
+
+impl DamagePipeline {
+    pub fn run(&self, context: &mut DamageContext, rng: &mut dyn RngCore) {
+      for stage in &self.stages {
+        stage.apply(context, rng);
+      }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn rng() -> StdRng {
+      StdRng::seed_from_u64(7)
+    }
+
+    /// A hit already at `amount`, as the later stages see it.
+    fn hit(amount: f32, defense: isize, affinity: Affinity) -> DamageContext {
+      DamageContext {
+        amount,
+        affinity,
+        ..DamageContext::new(amount as isize, defense)
+      }
+    }
+
+    #[test]
+    fn base_damage_starts_at_attack() {
+      let mut context = DamageContext::new(6, 2);
+      BaseDamage.apply(&mut context, &mut rng());
+      assert_eq!(context.amount, 6.0);
+    }
+
+    #[test]
+    fn buffs_add_attack_and_defense_bonuses() {
+      let mut context = hit(6.0, 2, Affinity::Normal);
+      context.attack_bonus = 2;
+      context.defense_bonus = 3;
+      Buffs.apply(&mut context, &mut rng());
+      assert_eq!(context.amount, 8.0);
+      assert_eq!(context.defense, 5);
+    }
+
+    #[test]
+    fn variance_stays_within_spread() {
+      let mut rng = rng();
+      let stage = Variance { spread: 0.1 };
+      for _ in 0..100 {
+        let mut context = hit(10.0, 0, Affinity::Normal);
+        stage.apply(&mut context, &mut rng);
+        assert!((9.0..=11.0).contains(&context.amount), "{}", context.amount);
+      }
+
+      let mut context = hit(10.0, 0, Affinity::Normal);
+      Variance { spread: 0.0 }.apply(&mut context, &mut rng);
+      assert_eq!(context.amount, 10.0);
+    }
+
+    #[test]
+    fn critical_multiplies_and_flags_the_hit() {
+      let mut context = hit(4.0, 0, Affinity::Normal);
+      Critical { chance: 1.0, multiplier: 2.0 }.apply(&mut context, &mut rng());
+      assert!(context.critical);
+      assert_eq!(context.amount, 8.0);
+
+      let mut context = hit(4.0, 0, Affinity::Normal);
+      Critical { chance: 0.0, multiplier: 2.0 }.apply(&mut context, &mut rng());
+      assert!(!context.critical);
+      assert_eq!(context.amount, 4.0);
+    }
+
+    #[test]
+    fn elemental_scales_by_affinity() {
+      let scaled = |affinity| {
+        let mut context = hit(10.0, 0, affinity);
+        Elemental.apply(&mut context, &mut rng());
+        context.amount
+      };
+      assert_eq!(scaled(Affinity::Weak), 20.0);
+      assert_eq!(scaled(Affinity::Normal), 10.0);
+      assert_eq!(scaled(Affinity::Resist), 5.0);
+      assert_eq!(scaled(Affinity::Immune), 0.0);
+      assert_eq!(scaled(Affinity::Absorb), 10.0);
+    }
+
+    #[test]
+    fn defense_mitigation_subtracts_down_to_the_floor() {
+      let mitigated = |amount, defense, affinity| {
+        let mut context = hit(amount, defense, affinity);
+        DefenseMitigation { floor: 1 }.apply(&mut context, &mut rng());
+        context.final_amount()
+      };
+      assert_eq!(mitigated(10.0, 4, Affinity::Normal), 6);
+      assert_eq!(mitigated(3.0, 10, Affinity::Normal), 1);
+      assert_eq!(mitigated(0.0, 10, Affinity::Immune), 0);
+    }
+
+    #[test]
+    fn pipeline_never_heals_through_high_defense() {
+      let pipeline = DamagePipeline::default();
+      let mut rng = rng();
+      for _ in 0..100 {
+        let mut context = DamageContext::new(3, 10);
+        pipeline.run(&mut context, &mut rng);
+        assert_eq!(context.final_amount(), MIN_DAMAGE);
+      }
+    }
+}
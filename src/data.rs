@@ -0,0 +1,15 @@
+use std::fs;
+
+use bevy::asset::FileAssetIo;
+use serde::de::DeserializeOwned;
+
+/// Reads a RON definition file from the assets folder. Game data is required to run, so a
+/// missing or malformed file panics with the path and the parse error.
+pub fn load_ron<T: DeserializeOwned>(path: &str) -> T {
+  let full_path = FileAssetIo::get_base_path().join("assets").join(path);
+  let source = fs::read_to_string(&full_path)
+    .unwrap_or_else(|error| panic!("Could not read {}: {}", full_path.display(), error));
+
+  ron::from_str(&source)
+    .unwrap_or_else(|error| panic!("Could not parse {}: {}", full_path.display(), error))
+}
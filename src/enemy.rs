@@ -1,9 +1,16 @@
+use std::collections::HashSet;
+use std::fmt;
+
 use bevy::prelude::*;
-use bevy_ecs_ldtk::prelude::FieldValue;
+use bevy_ecs_ldtk::prelude::{FieldValue, LdtkLevel};
 use bevy_ecs_ldtk::{LdtkEntity, EntityInstance};
 use bevy_ecs_ldtk::{prelude::GridCoords};
 use bevy_inspector_egui::Inspectable;
 
+use crate::animation::{AnimationClip, AnimationState, ClipEnd, SpriteAnimation};
+use crate::combat_stats::CombatStats;
+use crate::map::level_identifier;
+use crate::damage::{Affinities, Affinity, Element};
 use crate::{TILE_SIZE, AppState};
 
 #[derive(Component)]
@@ -12,70 +19,273 @@ pub struct Enemy;
 #[derive(Component, Default, Inspectable)]
 pub struct OverWorldEnemy;
 
+/// Points an overworld enemy back at the `Enemy_Spawn` entity it was created from.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct EnemySpawnLink {
+  pub spawn: Entity
+}
+
 pub struct EnemyPlugin;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct EnemySpawn;
 
+/// Order of the values in the LDtk `Stats` field: health, max health, attack, defense.
+const STATS_FIELD_LEN: usize = 4;
+pub const MAX_GROUP_SIZE: u32 = 4;
+
+const SLIME_TEXTURE: &str = "mystic_woods_free_v0.2/sprites/characters/slime.png";
+/// `slime.png` rows, each up to this many 32px frames wide: idle, hop, attack, hurt, death.
+const SLIME_COLUMNS: usize = 7;
+const SLIME_ROWS: usize = 5;
+const SLIME_FRAME_TIME: f32 = 0.12;
+
+pub fn slime_texture_atlas(asset_server: &AssetServer) -> TextureAtlas {
+  TextureAtlas::from_grid(asset_server.load(SLIME_TEXTURE), Vec2::splat(32.0), SLIME_COLUMNS, SLIME_ROWS, None, None)
+}
+
+pub fn slime_animation() -> SpriteAnimation {
+  SpriteAnimation::new(SLIME_FRAME_TIME)
+    .with_clip(AnimationState::Idle, None, AnimationClip::row(0, 4, ClipEnd::Loop))
+    .with_clip(
+      AnimationState::Hop,
+      None,
+      AnimationClip::row(SLIME_COLUMNS, 6, ClipEnd::Then(AnimationState::Idle))
+    )
+    .with_clip(AnimationState::Death, None, AnimationClip::row(SLIME_COLUMNS * 4, 5, ClipEnd::Hide))
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct EnemySpawnEntity {
+  /// LDtk instance id, used to remember the enemy once defeated.
+  pub iid: String,
   pub health: isize,
   pub max_health: isize,
   pub attack: isize,
   pub defense: isize,
-  pub name: String
+  pub name: String,
+  pub unescapable: bool,
+  pub experience: u32,
+  /// How many copies of this enemy show up in the fight.
+  pub count: u32,
+  pub affinities: Affinities,
+  /// Name of the loot table in `assets/data/loot.ron`, if the enemy drops anything.
+  pub loot: Option<String>
 }
 
-#[derive(Clone, Debug, Default, Bundle, LdtkEntity)]
-pub struct EnemySpawnBundle {
-    #[from_entity_instance]
-    enemy_spawn_entity: EnemySpawnEntity,
-    #[grid_coords]
-    grid_coords: GridCoords,
-}
+impl EnemySpawnEntity {
+    pub fn combat_stats(&self) -> CombatStats {
+      CombatStats {
+        health: self.health,
+        max_health: self.max_health,
+        attack: self.attack,
+        defense: self.defense,
+        mana: 0,
+        max_mana: 0,
+      }
+    }
+
+    pub fn from_entity_instance(
+      entity_instance: &EntityInstance,
+      level: &str
+    ) -> Result<Self, EnemySpawnError> {
+      let error = |kind| EnemySpawnError {
+        level: level.to_string(),
+        iid: entity_instance.iid.clone(),
+        kind
+      };
+
+      let field_value = |identifier: &str| {
+        entity_instance
+          .field_instances
+          .iter()
+          .find(|f| f.identifier == identifier)
+          .map(|f| &f.value)
+      };
+
+      let stats = match field_value("Stats") {
+        Some(FieldValue::Ints(values)) => values,
+        Some(_) => return Err(error(EnemySpawnErrorKind::WrongType("Stats"))),
+        None => return Err(error(EnemySpawnErrorKind::MissingField("Stats"))),
+      };
+
+      if stats.len() != STATS_FIELD_LEN {
+        return Err(error(EnemySpawnErrorKind::StatsLength(stats.len())));
+      }
+
+      let mut values = [0; STATS_FIELD_LEN];
+      for (index, value) in stats.iter().enumerate() {
+        match value {
+          Some(value) if *value >= 0 => values[index] = *value as isize,
+          Some(value) => return Err(error(EnemySpawnErrorKind::NegativeStat(index, *value))),
+          None => return Err(error(EnemySpawnErrorKind::EmptyStat(index))),
+        }
+      }
+
+      let name = match field_value("Name") {
+        Some(FieldValue::String(Some(text))) => text.clone(),
+        Some(FieldValue::String(None)) | None => {
+          return Err(error(EnemySpawnErrorKind::MissingField("Name")))
+        }
+        Some(_) => return Err(error(EnemySpawnErrorKind::WrongType("Name"))),
+      };
 
-impl From<EntityInstance> for EnemySpawnEntity {
-    fn from(entity_instance: EntityInstance) -> Self {
-      let field_instances = &entity_instance.field_instances;
-      if let Some(field_instance) =
-        field_instances.iter().find(|f| f.identifier == *"Name")
-      {
-        if let FieldValue::String(Some(text)) = &field_instance.value {
-          println!("Our name is {:?}", text);
-          EnemySpawnEntity {
-            health: 3,
-            max_health: 3,
-            attack: 3,
-            defense: 3,
-            name: "Name".to_string()
+      let unescapable = match field_value("Unescapable") {
+        Some(FieldValue::Bool(unescapable)) => *unescapable,
+        Some(_) => return Err(error(EnemySpawnErrorKind::WrongType("Unescapable"))),
+        None => false,
+      };
+
+      let experience = match field_value("Xp") {
+        Some(FieldValue::Int(Some(xp))) if *xp >= 0 => *xp as u32,
+        Some(FieldValue::Int(Some(xp))) => {
+          return Err(error(EnemySpawnErrorKind::NegativeValue("Xp", *xp)))
+        }
+        Some(FieldValue::Int(None)) | None => 0,
+        Some(_) => return Err(error(EnemySpawnErrorKind::WrongType("Xp"))),
+      };
+
+      let count = match field_value("Count") {
+        Some(FieldValue::Int(Some(count))) if (1..=MAX_GROUP_SIZE as i32).contains(count) => *count as u32,
+        Some(FieldValue::Int(Some(count))) => return Err(error(EnemySpawnErrorKind::GroupSize(*count))),
+        Some(FieldValue::Int(None)) | None => 1,
+        Some(_) => return Err(error(EnemySpawnErrorKind::WrongType("Count"))),
+      };
+
+      let affinities = match field_value("Affinities") {
+        Some(FieldValue::Strings(entries)) => {
+          let mut affinities = Affinities::default();
+          for entry in entries.iter().flatten() {
+            let (element, affinity) = parse_affinity(entry)
+              .ok_or_else(|| error(EnemySpawnErrorKind::Affinity(entry.clone())))?;
+            affinities.0.insert(element, affinity);
           }
-        } else {
-          EnemySpawnEntity {
-            health: 3,
-            max_health: 3,
-            attack: 3,
-            defense: 3,
-            name: "Name".to_string()
-          } 
+          affinities
         }
-      } else {
-        EnemySpawnEntity {
-          health: 3,
-          max_health: 3,
-          attack: 3,
-          defense: 3,
-          name: "Name".to_string()
+        Some(_) => return Err(error(EnemySpawnErrorKind::WrongType("Affinities"))),
+        None => Affinities::default(),
+      };
+
+      let loot = match field_value("Loot") {
+        Some(FieldValue::String(loot)) => loot.clone(),
+        Some(_) => return Err(error(EnemySpawnErrorKind::WrongType("Loot"))),
+        None => None,
+      };
+
+      let [health, max_health, attack, defense] = values;
+      Ok(EnemySpawnEntity {
+        iid: entity_instance.iid.clone(),
+        health,
+        max_health,
+        attack,
+        defense,
+        name,
+        unescapable,
+        experience,
+        count,
+        affinities,
+        loot
+      })
+    }
+}
+
+/// Reads an `Affinities` entry such as `Fire:Weak`.
+fn parse_affinity(entry: &str) -> Option<(Element, Affinity)> {
+  let (element, affinity) = entry.split_once(':')?;
+  let element = match element.trim() {
+    "Physical" => Element::Physical,
+    "Fire" => Element::Fire,
+    "Ice" => Element::Ice,
+    "Lightning" => Element::Lightning,
+    _ => return None,
+  };
+  let affinity = match affinity.trim() {
+    "Weak" => Affinity::Weak,
+    "Normal" => Affinity::Normal,
+    "Resist" => Affinity::Resist,
+    "Immune" => Affinity::Immune,
+    "Absorb" => Affinity::Absorb,
+    _ => return None,
+  };
+  Some((element, affinity))
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct EnemySpawnError {
+  /// Identifier of the LDtk level the spawn is in.
+  pub level: String,
+  pub iid: String,
+  pub kind: EnemySpawnErrorKind
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum EnemySpawnErrorKind {
+  MissingField(&'static str),
+  WrongType(&'static str),
+  StatsLength(usize),
+  EmptyStat(usize),
+  NegativeStat(usize, i32),
+  NegativeValue(&'static str, i32),
+  GroupSize(i32),
+  Affinity(String)
+}
+
+impl fmt::Display for EnemySpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      write!(f, "Enemy_Spawn {} in level {}: ", self.iid, self.level)?;
+      match &self.kind {
+        EnemySpawnErrorKind::MissingField(field) => write!(f, "missing \"{}\" field", field),
+        EnemySpawnErrorKind::WrongType(field) => write!(f, "\"{}\" field has the wrong type", field),
+        EnemySpawnErrorKind::StatsLength(len) => write!(
+          f,
+          "\"Stats\" must hold {} values (health, max health, attack, defense), found {}",
+          STATS_FIELD_LEN,
+          len
+        ),
+        EnemySpawnErrorKind::EmptyStat(index) => write!(f, "\"Stats\" value {} is empty", index),
+        EnemySpawnErrorKind::NegativeStat(index, value) => {
+          write!(f, "\"Stats\" value {} is negative ({})", index, value)
+        }
+        EnemySpawnErrorKind::NegativeValue(field, value) => {
+          write!(f, "\"{}\" is negative ({})", field, value)
+        }
+        EnemySpawnErrorKind::GroupSize(count) => {
+          write!(f, "\"Count\" must be between 1 and {}, found {}", MAX_GROUP_SIZE, count)
         }
+        EnemySpawnErrorKind::Affinity(entry) => write!(
+          f,
+          "\"Affinities\" entry \"{}\" must look like Element:Affinity, e.g. Fire:Weak",
+          entry
+        ),
       }
     }
 }
 
+impl std::error::Error for EnemySpawnError {}
+
+#[derive(Clone, Debug, Default, Bundle, LdtkEntity)]
+pub struct EnemySpawnBundle {
+    enemy_spawn: EnemySpawn,
+    #[from_entity_instance]
+    entity_instance: EntityInstance,
+    #[grid_coords]
+    grid_coords: GridCoords,
+}
+
+/// Instance ids of every `Enemy_Spawn` the party has beaten. Spawns in here stay empty
+/// when the level is reloaded.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct DefeatedEnemies(pub HashSet<String>);
+
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
       app
-      .add_startup_system(spawn_overworld_enemys)
+      .init_resource::<DefeatedEnemies>()
+      .add_system(parse_enemy_spawns)
       .add_system_set(
-        SystemSet::on_update(AppState::OverWorld).with_system(move_enemys_to_spawns)
+        SystemSet::on_update(AppState::OverWorld)
+          .with_system(spawn_overworld_enemys)
+          .with_system(despawn_orphaned_overworld_enemys)
       );
     }
 }
@@ -84,30 +294,208 @@ fn spawn_overworld_enemys(
   mut commands: Commands,
   asset_server: Res<AssetServer>,
   mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+  defeated: Res<DefeatedEnemies>,
+  enemy_spawn_query: Query<(Entity, &EnemySpawnEntity, &GridCoords), Added<EnemySpawnEntity>>,
 ) {
-  let texture_handle = asset_server.load("mystic_woods_free_v0.2/sprites/characters/slime.png");
-  let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(32.0, 32.0), 5, 7, None, None);
-  let texture_atlas_handle = texture_atlases.add(texture_atlas);
-  commands
-      .spawn(SpriteSheetBundle {
-          texture_atlas: texture_atlas_handle,
-            transform: Transform {
-              translation: Vec3::new(100.0,100.0, 900.0),
-              ..Default::default()
-          },
-          ..default()
-      })
-      .insert(OverWorldEnemy);
+  if enemy_spawn_query.is_empty() {
+    return;
+  }
+
+  let texture_atlas_handle = texture_atlases.add(slime_texture_atlas(&asset_server));
+
+  for (spawn, enemy_spawn, grid_coords) in enemy_spawn_query.iter() {
+    if defeated.0.contains(&enemy_spawn.iid) {
+      continue;
+    }
+    commands
+        .spawn(SpriteSheetBundle {
+            texture_atlas: texture_atlas_handle.clone(),
+              transform: Transform {
+                translation: Vec3::new(
+                  grid_coords.x as f32 * TILE_SIZE,
+                  grid_coords.y as f32 * TILE_SIZE,
+                  900.0
+                ),
+                ..Default::default()
+            },
+            ..default()
+        })
+        .insert(Name::new(enemy_spawn.name.clone()))
+        .insert(OverWorldEnemy)
+        .insert(slime_animation())
+        .insert(EnemySpawnLink { spawn });
+  }
+}
+
+/// Reads the fields of each new `Enemy_Spawn` into an `EnemySpawnEntity`. A spawn with bad
+/// fields is logged and removed so the rest of the level still loads.
+#[allow(clippy::type_complexity)]
+fn parse_enemy_spawns(
+  mut commands: Commands,
+  levels: Res<Assets<LdtkLevel>>,
+  level_query: Query<&Handle<LdtkLevel>>,
+  spawn_query: Query<(Entity, &EntityInstance, &Parent), (With<EnemySpawn>, Added<EntityInstance>)>
+) {
+  for (entity, entity_instance, parent) in spawn_query.iter() {
+    let level = level_identifier(parent, &level_query, &levels);
+    match EnemySpawnEntity::from_entity_instance(entity_instance, level) {
+      Ok(enemy_spawn) => {
+        commands.entity(entity).insert(enemy_spawn);
+      }
+      Err(error) => {
+        error!("Skipping invalid enemy spawn! {}", error);
+        commands.entity(entity).despawn_recursive();
+      }
+    }
+  }
 }
 
-fn move_enemys_to_spawns(
-    mut enemy_query: Query<&mut Transform, With<OverWorldEnemy>>,
-    enemy_spawn_query: Query<&GridCoords, Added<EnemySpawnEntity>>,
+/// Removes overworld enemies whose `Enemy_Spawn` went away, e.g. when the level is reloaded,
+/// or counts as defeated, e.g. after loading a save.
+fn despawn_orphaned_overworld_enemys(
+  mut commands: Commands,
+  defeated: Res<DefeatedEnemies>,
+  overworld_enemy_query: Query<(Entity, &EnemySpawnLink), With<OverWorldEnemy>>,
+  enemy_spawn_query: Query<&EnemySpawnEntity>,
 ) {
-    enemy_spawn_query.for_each(|&grid_coords| {
-        let mut enemy_transform = enemy_query.single_mut();
+  for (entity, link) in overworld_enemy_query.iter() {
+    if enemy_spawn_query.get(link.spawn).map_or(true, |spawn| defeated.0.contains(&spawn.iid)) {
+      commands.entity(entity).despawn_recursive();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_ecs_ldtk::ldtk::FieldInstance;
+
+    use super::*;
+
+    fn field(identifier: &str, value: FieldValue) -> FieldInstance {
+      FieldInstance {
+        identifier: identifier.to_string(),
+        tile: None,
+        field_instance_type: String::new(),
+        value,
+        def_uid: 0,
+        real_editor_values: Vec::new()
+      }
+    }
+
+    /// A valid slime spawn with `overrides` replacing or adding fields.
+    fn spawn_instance(overrides: Vec<FieldInstance>) -> EntityInstance {
+      let mut fields = vec![
+        field("Stats", FieldValue::Ints(vec![Some(5), Some(5), Some(2), Some(1)])),
+        field("Name", FieldValue::String(Some("Slime".to_string()))),
+      ];
+      for field in overrides {
+        fields.retain(|existing| existing.identifier != field.identifier);
+        fields.push(field);
+      }
+      EntityInstance {
+        iid: "spawn-iid".to_string(),
+        field_instances: fields,
+        ..default()
+      }
+    }
+
+    fn without(mut instance: EntityInstance, identifier: &str) -> EntityInstance {
+      instance.field_instances.retain(|field| field.identifier != identifier);
+      instance
+    }
+
+    fn error_kind(instance: &EntityInstance) -> EnemySpawnErrorKind {
+      EnemySpawnEntity::from_entity_instance(instance, "Level_0").unwrap_err().kind
+    }
 
-        enemy_transform.translation.x = grid_coords.x as f32 * TILE_SIZE;
-        enemy_transform.translation.y = grid_coords.y as f32 * TILE_SIZE;
-    });
-}
\ No newline at end of file
+    #[test]
+    fn parses_required_fields_with_defaults() {
+      let spawn = EnemySpawnEntity::from_entity_instance(&spawn_instance(vec![]), "Level_0").unwrap();
+
+      assert_eq!(spawn.iid, "spawn-iid");
+      assert_eq!(spawn.name, "Slime");
+      assert_eq!((spawn.health, spawn.max_health, spawn.attack, spawn.defense), (5, 5, 2, 1));
+      assert_eq!(spawn.count, 1);
+      assert_eq!(spawn.experience, 0);
+      assert!(!spawn.unescapable);
+      assert_eq!(spawn.loot, None);
+    }
+
+    #[test]
+    fn parses_optional_fields() {
+      let instance = spawn_instance(vec![
+        field("Unescapable", FieldValue::Bool(true)),
+        field("Xp", FieldValue::Int(Some(7))),
+        field("Count", FieldValue::Int(Some(MAX_GROUP_SIZE as i32))),
+        field("Affinities", FieldValue::Strings(vec![Some("Fire:Weak".to_string()), Some("Ice : Absorb".to_string())])),
+      ]);
+      let spawn = EnemySpawnEntity::from_entity_instance(&instance, "Level_0").unwrap();
+
+      assert!(spawn.unescapable);
+      assert_eq!(spawn.experience, 7);
+      assert_eq!(spawn.count, MAX_GROUP_SIZE);
+      assert_eq!(spawn.affinities.get(Element::Fire), Affinity::Weak);
+      assert_eq!(spawn.affinities.get(Element::Ice), Affinity::Absorb);
+      assert_eq!(spawn.affinities.get(Element::Lightning), Affinity::Normal);
+    }
+
+    #[test]
+    fn rejects_wrong_stats_length() {
+      let instance = spawn_instance(vec![field("Stats", FieldValue::Ints(vec![Some(5), Some(5), Some(2)]))]);
+      assert_eq!(error_kind(&instance), EnemySpawnErrorKind::StatsLength(3));
+    }
+
+    #[test]
+    fn rejects_negative_and_empty_stats() {
+      let negative = spawn_instance(vec![field("Stats", FieldValue::Ints(vec![Some(5), Some(5), Some(-1), Some(1)]))]);
+      assert_eq!(error_kind(&negative), EnemySpawnErrorKind::NegativeStat(2, -1));
+
+      let empty = spawn_instance(vec![field("Stats", FieldValue::Ints(vec![None, Some(5), Some(2), Some(1)]))]);
+      assert_eq!(error_kind(&empty), EnemySpawnErrorKind::EmptyStat(0));
+    }
+
+    #[test]
+    fn rejects_negative_experience() {
+      let instance = spawn_instance(vec![field("Xp", FieldValue::Int(Some(-3)))]);
+      assert_eq!(error_kind(&instance), EnemySpawnErrorKind::NegativeValue("Xp", -3));
+    }
+
+    #[test]
+    fn rejects_missing_fields() {
+      assert_eq!(error_kind(&without(spawn_instance(vec![]), "Stats")), EnemySpawnErrorKind::MissingField("Stats"));
+      assert_eq!(error_kind(&without(spawn_instance(vec![]), "Name")), EnemySpawnErrorKind::MissingField("Name"));
+    }
+
+    #[test]
+    fn rejects_wrong_field_types() {
+      let name = spawn_instance(vec![field("Name", FieldValue::Int(Some(1)))]);
+      assert_eq!(error_kind(&name), EnemySpawnErrorKind::WrongType("Name"));
+
+      let stats = spawn_instance(vec![field("Stats", FieldValue::String(Some("5".to_string())))]);
+      assert_eq!(error_kind(&stats), EnemySpawnErrorKind::WrongType("Stats"));
+
+      let unescapable = spawn_instance(vec![field("Unescapable", FieldValue::Int(Some(1)))]);
+      assert_eq!(error_kind(&unescapable), EnemySpawnErrorKind::WrongType("Unescapable"));
+    }
+
+    #[test]
+    fn rejects_group_sizes_out_of_range() {
+      let too_many = spawn_instance(vec![field("Count", FieldValue::Int(Some(MAX_GROUP_SIZE as i32 + 1)))]);
+      assert_eq!(error_kind(&too_many), EnemySpawnErrorKind::GroupSize(MAX_GROUP_SIZE as i32 + 1));
+
+      let none = spawn_instance(vec![field("Count", FieldValue::Int(Some(0)))]);
+      assert_eq!(error_kind(&none), EnemySpawnErrorKind::GroupSize(0));
+    }
+
+    #[test]
+    fn rejects_malformed_affinities() {
+      let instance = spawn_instance(vec![field("Affinities", FieldValue::Strings(vec![Some("Fire".to_string())]))]);
+      assert_eq!(error_kind(&instance), EnemySpawnErrorKind::Affinity("Fire".to_string()));
+    }
+
+    #[test]
+    fn error_message_locates_the_spawn() {
+      let error = EnemySpawnEntity::from_entity_instance(&without(spawn_instance(vec![]), "Name"), "Level_3").unwrap_err();
+      assert_eq!(error.to_string(), "Enemy_Spawn spawn-iid in level Level_3: missing \"Name\" field");
+    }
+}
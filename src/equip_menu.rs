@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+
+use crate::{AppState, FONT, actions::Action, combat_stats::{CombatStats, EffectiveStats}, player::Player};
+use crate::equipment::{effective_stats, Equipment, EquipmentBook, EquipmentSlot, SpareEquipment};
+use crate::status::StatusEffects;
+use crate::ui::{spawn_button, spawn_text, ButtonLayout, FONT_SIZE};
+
+pub struct EquipMenuPlugin;
+
+#[derive(Component, Inspectable)]
+pub struct EquipScreen;
+
+#[derive(Component, Inspectable)]
+pub struct EquipPreviewText;
+
+/// Picks `item` for `slot`; `None` takes off whatever is worn there.
+#[derive(Component)]
+pub struct EquipChoiceButton {
+  pub slot: EquipmentSlot,
+  pub item: Option<String>
+}
+
+#[derive(Component, Inspectable)]
+pub struct ConfirmEquipButton;
+
+#[derive(Component, Inspectable)]
+pub struct CloseEquipButton;
+
+/// The change being previewed, applied once confirmed.
+#[derive(Resource, Default)]
+pub struct PendingEquip(pub Option<(EquipmentSlot, Option<String>)>);
+
+impl Plugin for EquipMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<PendingEquip>()
+        .add_system_set(
+          SystemSet::on_update(AppState::OverWorld).with_system(open_equip_menu)
+        )
+        .add_system_set(
+          SystemSet::on_update(AppState::Equip)
+            .with_system(close_equip_menu)
+            .with_system(refresh_equip_screen)
+            .with_system(equip_choice_button_system)
+            .with_system(confirm_equip_button_system)
+            .with_system(update_equip_preview_text)
+        )
+        .add_system_set(
+          SystemSet::on_exit(AppState::Equip)
+            .with_system(despawn_equip_screen)
+            .with_system(clear_pending_equip)
+        );
+    }
+}
+
+/// `Equip` opens the equip screen on top of the overworld.
+fn open_equip_menu(
+  mut actions: ResMut<Input<Action>>,
+  mut state: ResMut<State<AppState>>
+) {
+  if actions.just_pressed(Action::Equip) && state.push(AppState::Equip).is_ok() {
+    actions.clear()
+  }
+}
+
+fn close_equip_menu(
+  mut actions: ResMut<Input<Action>>,
+  interaction_query: Query<&Interaction, (Changed<Interaction>, With<CloseEquipButton>)>,
+  mut state: ResMut<State<AppState>>
+) {
+  let clicked = interaction_query.iter().any(|interaction| *interaction == Interaction::Clicked);
+  if clicked || actions.any_just_pressed([Action::Equip, Action::Cancel]) {
+    state.pop().unwrap();
+
+    actions.clear()
+  }
+}
+
+/// Builds the screen on the first frame and rebuilds it whenever the player's gear changes,
+/// since the choices on offer depend on what is worn.
+#[allow(clippy::type_complexity)]
+fn refresh_equip_screen(
+    mut commands: Commands,
+    ass: Res<AssetServer>,
+    book: Res<EquipmentBook>,
+    screen_query: Query<Entity, With<EquipScreen>>,
+    player_query: Query<(&Equipment, &SpareEquipment), With<Player>>,
+    changed_query: Query<(), (With<Player>, Or<(Changed<Equipment>, Changed<SpareEquipment>)>)>
+) {
+    if !screen_query.is_empty() && changed_query.is_empty() {
+        return;
+    }
+    let Ok((equipment, spare)) = player_query.get_single() else {
+        return;
+    };
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let font = ass.load(FONT);
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                },
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Style::default()
+            },
+            background_color: BackgroundColor(Color::rgba(0.0, 0.0, 0.0, 0.85)),
+            ..NodeBundle::default()
+        })
+        .insert(EquipScreen)
+        .with_children(|parent| {
+            spawn_text(parent, font.clone(), "Equipment", FONT_SIZE);
+
+            for slot in EquipmentSlot::ALL {
+                let worn = equipment.get(slot).map_or("-", |item| item.as_str());
+                spawn_text(parent, font.clone(), &format!("{:?}: {}", slot, worn), FONT_SIZE);
+
+                let choices = spare
+                    .0
+                    .iter()
+                    .filter(|item| book.get(item).is_some_and(|definition| definition.slot == slot));
+                for item in choices {
+                    spawn_button(parent, font.clone(), item, ButtonLayout::MENU)
+                        .insert(EquipChoiceButton { slot, item: Some(item.clone()) });
+                }
+                if equipment.get(slot).is_some() {
+                    spawn_button(parent, font.clone(), &format!("Remove {:?}", slot), ButtonLayout::MENU)
+                        .insert(EquipChoiceButton { slot, item: None });
+                }
+            }
+
+            spawn_text(parent, font.clone(), "", FONT_SIZE).insert(EquipPreviewText);
+            spawn_button(parent, font.clone(), "Confirm", ButtonLayout::MENU).insert(ConfirmEquipButton);
+            spawn_button(parent, font, "Close", ButtonLayout::MENU).insert(CloseEquipButton);
+        });
+}
+
+fn equip_choice_button_system(
+    interaction_query: Query<(&Interaction, &EquipChoiceButton), Changed<Interaction>>,
+    mut pending: ResMut<PendingEquip>
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction == Interaction::Clicked {
+            pending.0 = Some((button.slot, button.item.clone()));
+        }
+    }
+}
+
+fn confirm_equip_button_system(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<ConfirmEquipButton>)>,
+    mut pending: ResMut<PendingEquip>,
+    mut player_query: Query<(&mut Equipment, &mut SpareEquipment), With<Player>>
+) {
+    for interaction in &interaction_query {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        let (Some((slot, item)), Ok((mut equipment, mut spare))) = (pending.0.take(), player_query.get_single_mut()) else {
+            continue;
+        };
+
+        if let Some(item) = &item {
+            let Some(index) = spare.0.iter().position(|spare_item| spare_item == item) else {
+                continue;
+            };
+            spare.0.remove(index);
+        }
+        if let Some(previous) = equipment.set(slot, item) {
+            spare.0.push(previous);
+        }
+    }
+}
+
+/// Shows the player's attack and defense, and what they would become with the pending change.
+fn update_equip_preview_text(
+    pending: Res<PendingEquip>,
+    book: Res<EquipmentBook>,
+    player_query: Query<(&CombatStats, &Equipment, Option<&StatusEffects>, &EffectiveStats), With<Player>>,
+    mut text_query: Query<&mut Text, With<EquipPreviewText>>
+) {
+    let Ok((stats, equipment, effects, current)) = player_query.get_single() else {
+        return;
+    };
+
+    let preview = match &pending.0 {
+        Some((slot, item)) => {
+            let mut preview_equipment = equipment.clone();
+            preview_equipment.set(*slot, item.clone());
+            let preview = effective_stats(stats, Some(&preview_equipment), effects, &book);
+            let name = item.clone().unwrap_or_else(|| format!("No {:?}", slot));
+            format!(
+                "{}: ATK {} -> {}  DEF {} -> {}",
+                name,
+                current.attack,
+                preview.attack,
+                current.defense,
+                preview.defense
+            )
+        }
+        None => format!("ATK {}  DEF {}", current.attack, current.defense),
+    };
+
+    for mut text in &mut text_query {
+        if text.sections[0].value != preview {
+            text.sections[0].value = preview.clone();
+        }
+    }
+}
+
+fn despawn_equip_screen(
+  mut commands: Commands,
+  screen_query: Query<Entity, With<EquipScreen>>
+) {
+  for entity in screen_query.iter() {
+    commands.entity(entity).despawn_recursive();
+  }
+}
+
+fn clear_pending_equip(mut pending: ResMut<PendingEquip>) {
+  pending.0 = None;
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{combat_stats::{CombatStats, EffectiveStats}, data::load_ron, status::StatusEffects};
+
+const EQUIPMENT_PATH: &str = "data/equipment.ron";
+
+pub struct EquipmentPlugin;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EquipmentSlot {
+  Weapon,
+  Armor,
+  Accessory
+}
+
+impl EquipmentSlot {
+    pub const ALL: [EquipmentSlot; 3] = [EquipmentSlot::Weapon, EquipmentSlot::Armor, EquipmentSlot::Accessory];
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct EquipmentDefinition {
+  pub name: String,
+  pub slot: EquipmentSlot,
+  #[serde(default)]
+  pub attack: isize,
+  #[serde(default)]
+  pub defense: isize
+}
+
+/// Every equipment definition from `assets/data/equipment.ron`, by name.
+#[derive(Resource, Default)]
+pub struct EquipmentBook {
+  equipment: HashMap<String, EquipmentDefinition>
+}
+
+impl EquipmentBook {
+    pub fn from_definitions(definitions: Vec<EquipmentDefinition>) -> Self {
+      EquipmentBook {
+        equipment: definitions
+          .into_iter()
+          .map(|equipment| (equipment.name.clone(), equipment))
+          .collect()
+      }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&EquipmentDefinition> {
+      self.equipment.get(name)
+    }
+}
+
+/// What a combatant is wearing, by slot.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Equipment {
+  pub weapon: Option<String>,
+  pub armor: Option<String>,
+  pub accessory: Option<String>
+}
+
+impl Equipment {
+    pub fn get(&self, slot: EquipmentSlot) -> Option<&String> {
+      match slot {
+        EquipmentSlot::Weapon => self.weapon.as_ref(),
+        EquipmentSlot::Armor => self.armor.as_ref(),
+        EquipmentSlot::Accessory => self.accessory.as_ref(),
+      }
+    }
+
+    /// Puts `item` in `slot`, handing back whatever was there before.
+    pub fn set(&mut self, slot: EquipmentSlot, item: Option<String>) -> Option<String> {
+      let current = match slot {
+        EquipmentSlot::Weapon => &mut self.weapon,
+        EquipmentSlot::Armor => &mut self.armor,
+        EquipmentSlot::Accessory => &mut self.accessory,
+      };
+      std::mem::replace(current, item)
+    }
+
+    fn worn<'a>(&'a self, book: &'a EquipmentBook) -> impl Iterator<Item = &'a EquipmentDefinition> {
+      EquipmentSlot::ALL
+        .into_iter()
+        .filter_map(move |slot| self.get(slot))
+        .filter_map(|name| book.get(name))
+    }
+}
+
+/// Gear the party is carrying but nobody is wearing.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpareEquipment(pub Vec<String>);
+
+/// Base stats plus everything worn plus status bonuses such as Focus and Guard.
+pub fn effective_stats(
+  stats: &CombatStats,
+  equipment: Option<&Equipment>,
+  effects: Option<&StatusEffects>,
+  book: &EquipmentBook
+) -> EffectiveStats {
+  let mut effective = EffectiveStats::base(stats);
+  for item in equipment.into_iter().flat_map(|equipment| equipment.worn(book)) {
+    effective.attack += item.attack;
+    effective.defense += item.defense;
+  }
+  if let Some(effects) = effects {
+    effective.attack_bonus = effects.attack_bonus();
+    effective.defense_bonus = effects.defense_bonus();
+  }
+  effective.attack += effective.attack_bonus;
+  effective.defense += effective.defense_bonus;
+  effective
+}
+
+impl Plugin for EquipmentPlugin {
+    fn build(&self, app: &mut App) {
+        app
+          .insert_resource(EquipmentBook::from_definitions(load_ron(EQUIPMENT_PATH)))
+          .add_system(update_effective_stats);
+    }
+}
+
+fn update_effective_stats(
+  book: Res<EquipmentBook>,
+  mut stats_query: Query<(&CombatStats, Option<&Equipment>, Option<&StatusEffects>, &mut EffectiveStats)>
+) {
+  for (stats, equipment, effects, mut effective) in &mut stats_query {
+    let updated = effective_stats(stats, equipment, effects, &book);
+    if *effective != updated {
+      *effective = updated;
+    }
+  }
+}
@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use serde::{Deserialize, Serialize};
+
+use crate::combat_stats::CombatStats;
+
+pub const MAX_LEVEL: u32 = 10;
+
+/// Stats gained on reaching a level. Index 0 is the growth for reaching level 2.
+const STAT_GROWTH: [StatGrowth; (MAX_LEVEL - 1) as usize] = [
+  StatGrowth { health: 2, attack: 1, defense: 0 },
+  StatGrowth { health: 2, attack: 0, defense: 1 },
+  StatGrowth { health: 3, attack: 1, defense: 0 },
+  StatGrowth { health: 2, attack: 1, defense: 1 },
+  StatGrowth { health: 3, attack: 1, defense: 0 },
+  StatGrowth { health: 3, attack: 0, defense: 1 },
+  StatGrowth { health: 4, attack: 1, defense: 1 },
+  StatGrowth { health: 4, attack: 1, defense: 1 },
+  StatGrowth { health: 5, attack: 2, defense: 1 },
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatGrowth {
+  pub health: isize,
+  pub attack: isize,
+  pub defense: isize
+}
+
+/// Experience granted to the player for defeating this enemy.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct ExperienceReward(pub u32);
+
+/// `xp` counts progress towards the next level and resets on every level up.
+#[derive(Component, Inspectable, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Experience {
+  pub level: u32,
+  pub xp: u32
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+      Experience { level: 1, xp: 0 }
+    }
+}
+
+impl Experience {
+    /// Adds `amount` experience, applying stat growth for every level reached.
+    /// Returns the levels reached, in order.
+    pub fn gain(&mut self, amount: u32, stats: &mut CombatStats) -> Vec<u32> {
+      let mut levels_reached = Vec::new();
+      if self.level >= MAX_LEVEL {
+        return levels_reached;
+      }
+
+      self.xp += amount;
+      while self.level < MAX_LEVEL && self.xp >= xp_to_next_level(self.level) {
+        self.xp -= xp_to_next_level(self.level);
+        self.level += 1;
+        stat_growth(self.level).apply(stats);
+        levels_reached.push(self.level);
+      }
+
+      if self.level == MAX_LEVEL {
+        self.xp = 0;
+      }
+      levels_reached
+    }
+}
+
+/// Experience needed to go from `level` to `level + 1`: 10, 30, 60, 100, ...
+pub fn xp_to_next_level(level: u32) -> u32 {
+  5 * level * (level + 1)
+}
+
+pub fn stat_growth(level: u32) -> StatGrowth {
+  let index = level.saturating_sub(2) as usize;
+  STAT_GROWTH[index.min(STAT_GROWTH.len() - 1)]
+}
+
+impl StatGrowth {
+    pub fn apply(&self, stats: &mut CombatStats) {
+      stats.max_health += self.health;
+      stats.health += self.health;
+      stats.attack += self.attack;
+      stats.defense += self.defense;
+    }
+}
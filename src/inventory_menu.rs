@@ -0,0 +1,249 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+
+use crate::{AppState, FONT, actions::Action, combat_stats::CombatStats, party::Party, player::Player};
+use crate::items::{Inventory, ItemBook, ItemUse, ItemUsedEvent};
+use crate::loot::Wallet;
+use crate::status::StatusEffects;
+use crate::ui::{spawn_button, spawn_text, ButtonLayout, FONT_SIZE};
+
+pub struct InventoryMenuPlugin;
+
+#[derive(Component, Inspectable)]
+pub struct InventoryScreen;
+
+#[derive(Component, Inspectable)]
+pub struct InventoryPartyText;
+
+#[derive(Component, Inspectable)]
+pub struct InventoryInfoText;
+
+#[derive(Component)]
+pub struct InventoryItemButton {
+  pub item: String
+}
+
+/// Uses the selected item on this party member.
+#[derive(Component)]
+pub struct UseOnButton {
+  pub member: Entity
+}
+
+#[derive(Component, Inspectable)]
+pub struct CloseInventoryButton;
+
+/// The item picked on the inventory screen, waiting for a party member to use it on.
+#[derive(Resource, Default)]
+pub struct SelectedItem(pub Option<String>);
+
+impl Plugin for InventoryMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<SelectedItem>()
+        .add_system_set(
+          SystemSet::on_update(AppState::OverWorld).with_system(open_inventory)
+        )
+        .add_system_set(
+          SystemSet::on_enter(AppState::Inventory).with_system(build_inventory_screen)
+        )
+        .add_system_set(
+          SystemSet::on_update(AppState::Inventory)
+            .with_system(close_inventory)
+            .with_system(inventory_item_button_system)
+            .with_system(use_on_button_system)
+            .with_system(update_inventory_party_text)
+            .with_system(update_inventory_item_buttons)
+            .with_system(update_inventory_info_text)
+        )
+        .add_system_set(
+          SystemSet::on_exit(AppState::Inventory)
+            .with_system(despawn_inventory_screen)
+            .with_system(clear_selected_item)
+        );
+    }
+}
+
+/// `Inventory` opens the inventory on top of the overworld, which stays paused underneath.
+fn open_inventory(
+  mut actions: ResMut<Input<Action>>,
+  mut state: ResMut<State<AppState>>
+) {
+  // Loses to any transition already queued this frame, such as a fight starting.
+  if actions.just_pressed(Action::Inventory) && state.push(AppState::Inventory).is_ok() {
+    actions.clear()
+  }
+}
+
+fn close_inventory(
+  mut actions: ResMut<Input<Action>>,
+  interaction_query: Query<&Interaction, (Changed<Interaction>, With<CloseInventoryButton>)>,
+  mut state: ResMut<State<AppState>>
+) {
+  let clicked = interaction_query.iter().any(|interaction| *interaction == Interaction::Clicked);
+  if clicked || actions.any_just_pressed([Action::Inventory, Action::Cancel]) {
+    state.pop().unwrap();
+
+    actions.clear()
+  }
+}
+
+fn build_inventory_screen(
+    mut commands: Commands,
+    ass: Res<AssetServer>,
+    party: Res<Party>,
+    name_query: Query<&Name>,
+    inventory_query: Query<&Inventory, With<Player>>
+) {
+    let font = ass.load(FONT);
+    let stacks = inventory_query.get_single().map(|inventory| inventory.stacks.clone()).unwrap_or_default();
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                },
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Style::default()
+            },
+            background_color: BackgroundColor(Color::rgba(0.0, 0.0, 0.0, 0.85)),
+            ..NodeBundle::default()
+        })
+        .insert(InventoryScreen)
+        .with_children(|parent| {
+            spawn_text(parent, font.clone(), "Inventory", FONT_SIZE);
+            spawn_text(parent, font.clone(), "", FONT_SIZE).insert(InventoryPartyText);
+
+            for stack in &stacks {
+                spawn_button(parent, font.clone(), &format!("{} x{}", stack.item, stack.count), ButtonLayout::MENU)
+                    .insert(InventoryItemButton { item: stack.item.clone() });
+            }
+
+            spawn_text(parent, font.clone(), "Pick an item", FONT_SIZE).insert(InventoryInfoText);
+
+            for &member in &party.members {
+                let name = name_query.get(member).map_or("?".to_string(), |name| name.to_string());
+                spawn_button(parent, font.clone(), &format!("Use on {}", name), ButtonLayout::MENU)
+                    .insert(UseOnButton { member });
+            }
+
+            spawn_button(parent, font, "Close", ButtonLayout::MENU).insert(CloseInventoryButton);
+        });
+}
+
+fn inventory_item_button_system(
+    interaction_query: Query<(&Interaction, &InventoryItemButton), Changed<Interaction>>,
+    item_book: Res<ItemBook>,
+    mut selected: ResMut<SelectedItem>,
+    mut info_query: Query<&mut Text, With<InventoryInfoText>>
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        selected.0 = Some(button.item.clone());
+
+        let description = item_book.get(&button.item).map_or("", |item| item.description.as_str());
+        for mut text in &mut info_query {
+            text.sections[0].value = format!("{}: {}", button.item, description);
+        }
+    }
+}
+
+fn use_on_button_system(
+    interaction_query: Query<(&Interaction, &UseOnButton), Changed<Interaction>>,
+    selected: Res<SelectedItem>,
+    mut stats_query: Query<&mut CombatStats>,
+    mut item_use: ItemUse
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        let (Some(item), Ok(mut stats)) = (&selected.0, stats_query.get_mut(button.member)) else {
+            continue;
+        };
+        item_use.use_item(item, button.member, &mut stats);
+    }
+}
+
+fn update_inventory_party_text(
+  mut text_query: Query<&mut Text, With<InventoryPartyText>>,
+  member_query: Query<(&Name, &CombatStats, &StatusEffects)>,
+  wallet_query: Query<&Wallet, With<Player>>,
+  party: Res<Party>
+) {
+  let gold = wallet_query.get_single().map_or(0, |wallet| wallet.gold);
+  let members = party
+    .members
+    .iter()
+    .filter_map(|&member| {
+      let (name, stats, effects) = member_query.get(member).ok()?;
+      Some(format!(
+        "{}: {}/{} MP {}/{} {}",
+        name,
+        stats.health,
+        stats.max_health,
+        stats.mana,
+        stats.max_mana,
+        effects.icons()
+      ))
+    })
+    .collect::<Vec<_>>()
+    .join("   ");
+  let status = format!("{}   Gold: {}", members, gold);
+
+  for mut text in &mut text_query {
+      text.sections[0].value = status.clone();
+  }
+}
+
+/// Keeps the counts on the item buttons current and hides stacks that ran out.
+fn update_inventory_item_buttons(
+    inventory_query: Query<&Inventory, (With<Player>, Changed<Inventory>)>,
+    mut button_query: Query<(&InventoryItemButton, &Children, &mut Style)>,
+    mut text_query: Query<&mut Text>
+) {
+    let Ok(inventory) = inventory_query.get_single() else {
+        return;
+    };
+
+    for (button, children, mut style) in &mut button_query {
+        let count = inventory.count(&button.item);
+        if let Ok(mut text) = text_query.get_mut(children[0]) {
+            text.sections[0].value = format!("{} x{}", button.item, count);
+        }
+        if count == 0 {
+            style.display = Display::None;
+        }
+    }
+}
+
+fn update_inventory_info_text(
+    mut item_used: EventReader<ItemUsedEvent>,
+    name_query: Query<&Name>,
+    mut info_query: Query<&mut Text, With<InventoryInfoText>>
+) {
+    for event in item_used.iter() {
+        let target = name_query.get(event.target).map_or("?".to_string(), |name| name.to_string());
+        for mut text in &mut info_query {
+            text.sections[0].value = format!("{}: {} {}", event.item, target, event.outcome);
+        }
+    }
+}
+
+fn despawn_inventory_screen(
+  mut commands: Commands,
+  screen_query: Query<Entity, With<InventoryScreen>>
+) {
+  for entity in screen_query.iter() {
+    commands.entity(entity).despawn_recursive();
+  }
+}
+
+fn clear_selected_item(mut selected: ResMut<SelectedItem>) {
+  selected.0 = None;
+}
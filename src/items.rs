@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{combat_stats::CombatStats, data::load_ron, player::Player, status::{StatusEffects, StatusKind}};
+
+const ITEMS_PATH: &str = "data/items.ron";
+
+pub struct ItemsPlugin;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ItemEffect {
+  Heal(isize),
+  RestoreMana(isize),
+  Cure(StatusKind)
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ItemDefinition {
+  pub name: String,
+  pub description: String,
+  pub effect: ItemEffect
+}
+
+/// Every item definition from `assets/data/items.ron`, by name.
+#[derive(Resource, Default)]
+pub struct ItemBook {
+  items: HashMap<String, ItemDefinition>
+}
+
+impl ItemBook {
+    pub fn from_definitions(definitions: Vec<ItemDefinition>) -> Self {
+      ItemBook {
+        items: definitions
+          .into_iter()
+          .map(|item| (item.name.clone(), item))
+          .collect()
+      }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ItemDefinition> {
+      self.items.get(name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ItemStack {
+  pub item: String,
+  pub count: u32
+}
+
+/// Items carried by the party, kept on the player. Empty stacks are dropped.
+#[derive(Component, Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Inventory {
+  pub stacks: Vec<ItemStack>
+}
+
+impl Inventory {
+    pub fn add(&mut self, item: &str, count: u32) {
+      match self.stacks.iter_mut().find(|stack| stack.item == item) {
+        Some(stack) => stack.count += count,
+        None => self.stacks.push(ItemStack { item: item.to_string(), count }),
+      }
+    }
+
+    /// Takes one `item` out, returning `false` if there was none.
+    pub fn remove(&mut self, item: &str) -> bool {
+      let Some(index) = self.stacks.iter().position(|stack| stack.item == item) else {
+        return false;
+      };
+
+      self.stacks[index].count -= 1;
+      if self.stacks[index].count == 0 {
+        self.stacks.remove(index);
+      }
+      true
+    }
+
+    pub fn count(&self, item: &str) -> u32 {
+      self
+        .stacks
+        .iter()
+        .find(|stack| stack.item == item)
+        .map_or(0, |stack| stack.count)
+    }
+}
+
+/// Sent whenever an item is tried, with a line like "recovers 5 health".
+pub struct ItemUsedEvent {
+  pub target: Entity,
+  pub item: String,
+  pub outcome: String
+}
+
+/// Everything needed to use an item from the player's inventory on a party member.
+#[derive(SystemParam)]
+pub struct ItemUse<'w, 's> {
+  book: Res<'w, ItemBook>,
+  inventory_query: Query<'w, 's, &'static mut Inventory, With<Player>>,
+  effects_query: Query<'w, 's, &'static mut StatusEffects>,
+  item_used: EventWriter<'w, 's, ItemUsedEvent>
+}
+
+impl<'w, 's> ItemUse<'w, 's> {
+    /// Applies `item` to `target` and uses it up. Items that would do nothing, like a potion
+    /// at full health, are kept and `false` is returned.
+    pub fn use_item(&mut self, item: &str, target: Entity, target_stats: &mut CombatStats) -> bool {
+      let Some(definition) = self.book.get(item) else {
+        return false;
+      };
+      let Ok(mut inventory) = self.inventory_query.get_single_mut() else {
+        return false;
+      };
+      if inventory.count(item) == 0 || target_stats.health == 0 {
+        return false;
+      }
+
+      let outcome = match definition.effect {
+        ItemEffect::Heal(amount) => {
+          let healed = target_stats.heal(amount);
+          (healed > 0).then(|| format!("recovers {} health", healed))
+        }
+        ItemEffect::RestoreMana(amount) => {
+          let restored = target_stats.restore_mana(amount);
+          (restored > 0).then(|| format!("recovers {} MP", restored))
+        }
+        ItemEffect::Cure(kind) => self
+          .effects_query
+          .get_mut(target)
+          .is_ok_and(|mut effects| effects.remove(kind))
+          .then(|| format!("is cured of {:?}", kind)),
+      };
+
+      let consumed = outcome.is_some();
+      if consumed {
+        inventory.remove(item);
+      }
+      self.item_used.send(ItemUsedEvent {
+        target,
+        item: item.to_string(),
+        outcome: outcome.unwrap_or_else(|| "is not affected".to_string())
+      });
+      consumed
+    }
+}
+
+impl Plugin for ItemsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+          .add_event::<ItemUsedEvent>()
+          .insert_resource(ItemBook::from_definitions(load_ron(ITEMS_PATH)));
+    }
+}
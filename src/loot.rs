@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+use serde::Deserialize;
+
+use crate::data::load_ron;
+use crate::equipment::{EquipmentBook, SpareEquipment};
+use crate::items::{Inventory, ItemBook};
+
+const LOOT_PATH: &str = "data/loot.ron";
+
+pub struct LootPlugin;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LootDrop {
+  /// An item or a piece of equipment.
+  pub item: String,
+  pub weight: u32
+}
+
+/// What one defeated enemy can leave behind: some gold in `gold` (inclusive), and with
+/// `drop_chance` one of `drops`, picked by weight.
+#[derive(Deserialize, Clone, Debug)]
+pub struct LootTable {
+  pub gold: (u32, u32),
+  #[serde(default)]
+  pub drop_chance: f32,
+  #[serde(default)]
+  pub drops: Vec<LootDrop>
+}
+
+impl LootTable {
+    pub fn roll(&self, rng: &mut impl Rng) -> (u32, Option<String>) {
+      let (min, max) = self.gold;
+      let gold = rng.gen_range(min.min(max)..=max.max(min));
+
+      let item = if rng.gen::<f32>() < self.drop_chance {
+        self
+          .drops
+          .choose_weighted(rng, |drop| drop.weight)
+          .ok()
+          .map(|drop| drop.item.clone())
+      } else {
+        None
+      };
+      (gold, item)
+    }
+}
+
+/// Every loot table from `assets/data/loot.ron`, by the name enemies refer to them with.
+#[derive(Resource, Default)]
+pub struct LootTables(pub HashMap<String, LootTable>);
+
+/// The loot table a combat enemy rolls on when it is defeated.
+#[derive(Component, Clone, Debug)]
+pub struct Loot(pub String);
+
+/// Gold carried by the party, kept on the player.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Wallet {
+  pub gold: u32
+}
+
+/// Puts a found item in the inventory, or with the spare gear if it is equipment.
+/// Returns `false` for names neither book knows.
+pub fn store_item(
+  item: &str,
+  item_book: &ItemBook,
+  equipment_book: &EquipmentBook,
+  inventory: &mut Inventory,
+  spare: &mut SpareEquipment
+) -> bool {
+  if item_book.get(item).is_some() {
+    inventory.add(item, 1);
+  } else if equipment_book.get(item).is_some() {
+    spare.0.push(item.to_string());
+  } else {
+    return false;
+  }
+  true
+}
+
+impl Plugin for LootPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LootTables(load_ron(LOOT_PATH)));
+    }
+}
@@ -6,39 +6,102 @@ use bevy_inspector_egui::WorldInspectorPlugin;
 
 pub const CLEAR: Color = Color::rgb(0.1, 0.1, 0.1);
 pub const RESOLUTION: f32 = 16.0 / 9.0;
+/// Height of the game view in world pixels; the width follows from `RESOLUTION`. The view is
+/// drawn at the largest whole multiple that fits the window.
+pub const VIEW_HEIGHT: f32 = 270.0;
+pub const VIEW_WIDTH: f32 = VIEW_HEIGHT * RESOLUTION;
 pub const TILE_SIZE: f32 = 16.0;
+pub const FONT: &str = "fonts/BebasNeue-Regular.ttf";
 
+mod actions;
+mod animation;
+mod camera;
 mod combat;
 mod combat_menu;
+mod combat_result;
 mod combat_stats;
+mod damage;
+mod data;
+mod chest;
 mod collider;
+mod controls_menu;
 mod enemy;
+mod equip_menu;
+mod equipment;
+mod experience;
+mod inventory_menu;
+mod items;
+mod loot;
 mod map;
+mod menu_focus;
+mod notice;
+mod party;
 mod systems;
 mod player;
+mod rng;
+mod save;
+mod skills;
+mod status;
+mod ui;
 mod wall;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 enum AppState {
     OverWorld,
-    Combat
+    Combat,
+    Victory,
+    GameOver,
+    Inventory,
+    Equip,
+    Controls
 }
 
 fn main() {
     App::new()
         .add_state(AppState::OverWorld)
-        .add_plugins(DefaultPlugins)
+        .add_plugins(
+          DefaultPlugins
+            .set(WindowPlugin {
+              window: WindowDescriptor {
+                title: "Chat RPG".to_string(),
+                width: VIEW_WIDTH * 2.0,
+                height: VIEW_HEIGHT * 2.0,
+                ..default()
+              },
+              ..default()
+            })
+            .set(ImagePlugin::default_nearest())
+        )
         .add_plugin(WorldInspectorPlugin::new())
         .add_plugin(LdtkPlugin)
+        .add_plugin(rng::RngPlugin)
+        .add_plugin(actions::ActionsPlugin::default())
+        .add_plugin(menu_focus::MenuFocusPlugin)
+        .add_plugin(animation::AnimationPlugin)
+        .add_plugin(notice::NoticePlugin)
+        .add_plugin(skills::SkillsPlugin)
+        .add_plugin(status::StatusPlugin)
+        .add_plugin(items::ItemsPlugin)
+        .add_plugin(equipment::EquipmentPlugin)
+        .add_plugin(loot::LootPlugin)
+        .add_plugin(chest::ChestPlugin)
         .add_startup_system(systems::spawn_camera)
+        .add_plugin(camera::CameraPlugin)
         .insert_resource(LevelSelection::Index(0))
         .add_plugin(map::MapPlugin)
         .add_plugin(player::PlayerPlugin)
+        .add_plugin(party::PartyPlugin)
         .add_plugin(enemy::EnemyPlugin)
         .add_plugin(combat::CombatPlugin)
         .add_plugin(combat_menu::CombatMenuPlugin)
+        .add_plugin(combat_result::CombatResultPlugin)
+        .add_plugin(inventory_menu::InventoryMenuPlugin)
+        .add_plugin(equip_menu::EquipMenuPlugin)
+        .add_plugin(controls_menu::ControlsMenuPlugin)
+        .add_plugin(save::SavePlugin)
         .register_ldtk_entity::<player::PlayerSpawnBundle>("Player_spawn")
         .register_ldtk_entity::<enemy::EnemySpawnBundle>("Enemy_Spawn")
+        .register_ldtk_entity::<chest::ChestBundle>("Chest")
         .register_ldtk_int_cell::<wall::WallBundle>(1)
         .run();
 }
\ No newline at end of file
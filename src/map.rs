@@ -21,6 +21,20 @@ impl Plugin for MapPlugin {
     }
 }
 
+/// Identifier of the LDtk level an entity spawned from the map sits in, for messages about
+/// it. `parent` is the entity's `Parent`, which LDtk sets to the level.
+pub fn level_identifier<'a>(
+  parent: &Parent,
+  level_query: &Query<&Handle<LdtkLevel>>,
+  levels: &'a Assets<LdtkLevel>
+) -> &'a str {
+  level_query
+    .get(parent.get())
+    .ok()
+    .and_then(|handle| levels.get(handle))
+    .map_or("?", |level| level.level.identifier.as_str())
+}
+
 pub fn spawn_map(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.spawn(LdtkWorldBundle {
         ldtk_handle: asset_server.load("Untitled.ldtk"),
@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+
+use crate::actions::Action;
+use crate::combat_menu::TargetSelection;
+use crate::controls_menu::PendingRebind;
+
+const FOCUSED_TEXT: Color = Color::rgb(1.0, 0.85, 0.3);
+
+pub struct MenuFocusPlugin;
+
+/// The button keyboard and gamepad navigation is on, and the one it clicked last frame.
+#[derive(Resource, Default)]
+pub struct MenuFocus {
+  focused: Option<Entity>,
+  clicked: Option<Entity>
+}
+
+impl Plugin for MenuFocusPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<MenuFocus>()
+        .add_system(navigate_menu)
+        .add_system(highlight_focused_button.after(navigate_menu));
+    }
+}
+
+/// Moves the focus between the buttons on screen, top to bottom, and clicks the focused one
+/// on `Confirm`. Buttons react to it the same way as to a mouse click.
+fn navigate_menu(
+  mut actions: ResMut<Input<Action>>,
+  selection: Res<TargetSelection>,
+  pending_rebind: Res<PendingRebind>,
+  mut focus: ResMut<MenuFocus>,
+  mut button_query: Query<(Entity, &Node, &GlobalTransform, &mut Interaction), With<Button>>
+) {
+  // The mouse never lets go of a button clicked this way, so do it here.
+  if let Some(clicked) = focus.clicked.take() {
+    if let Ok((_, _, _, mut interaction)) = button_query.get_mut(clicked) {
+      if *interaction == Interaction::Clicked {
+        *interaction = Interaction::None;
+      }
+    }
+  }
+  // Target selection and rebinding have their own use for the same input.
+  if selection.active || pending_rebind.0.is_some() {
+    return;
+  }
+
+  let step: isize = if actions.any_just_pressed([Action::MoveUp, Action::MoveLeft]) {
+    -1
+  } else if actions.any_just_pressed([Action::MoveDown, Action::MoveRight]) {
+    1
+  } else {
+    0
+  };
+  let confirm = actions.just_pressed(Action::Confirm);
+  if step == 0 && !confirm {
+    return;
+  }
+
+  // Hidden buttons are laid out with no size.
+  let mut buttons: Vec<(Entity, Vec3)> = button_query
+    .iter()
+    .filter(|(_, node, _, _)| node.size().min_element() > 0.0)
+    .map(|(entity, _, transform, _)| (entity, transform.translation()))
+    .collect();
+  if buttons.is_empty() {
+    focus.focused = None;
+    return;
+  }
+  buttons.sort_by(|(_, a), (_, b)| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+
+  let current = focus.focused.and_then(|focused| buttons.iter().position(|(entity, _)| *entity == focused));
+  let Some(current) = current else {
+    focus.focused = Some(buttons[0].0);
+    return;
+  };
+
+  if step != 0 {
+    let next = (current as isize + step).rem_euclid(buttons.len() as isize) as usize;
+    focus.focused = Some(buttons[next].0);
+  } else if let Ok((entity, _, _, mut interaction)) = button_query.get_mut(buttons[current].0) {
+    *interaction = Interaction::Clicked;
+    focus.clicked = Some(entity);
+    actions.clear();
+  }
+}
+
+fn highlight_focused_button(
+  focus: Res<MenuFocus>,
+  button_query: Query<(Entity, &Children), With<Button>>,
+  mut text_query: Query<&mut Text>
+) {
+  for (entity, children) in &button_query {
+    let color = if focus.focused == Some(entity) { FOCUSED_TEXT } else { Color::WHITE };
+    for &child in children.iter() {
+      let Ok(mut text) = text_query.get_mut(child) else {
+        continue;
+      };
+      for section in &mut text.sections {
+        if section.style.color != color {
+          section.style.color = color;
+        }
+      }
+    }
+  }
+}
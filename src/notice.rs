@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+
+use crate::FONT;
+
+const NOTICE_TIME: f32 = 2.5;
+
+pub struct NoticePlugin;
+
+/// Shows a line of text in the corner of the screen for a moment, e.g. "Game saved".
+pub struct NoticeEvent(pub String);
+
+#[derive(Component)]
+struct Notice(Timer);
+
+impl Plugin for NoticePlugin {
+    fn build(&self, app: &mut App) {
+        app
+          .add_event::<NoticeEvent>()
+          .add_system(show_notice)
+          .add_system(expire_notice);
+    }
+}
+
+/// Newer notices replace the text of the one on screen rather than stacking up.
+fn show_notice(
+  mut commands: Commands,
+  mut notice_event: EventReader<NoticeEvent>,
+  ass: Res<AssetServer>,
+  mut notice_query: Query<(&mut Text, &mut Notice)>
+) {
+  let Some(NoticeEvent(message)) = notice_event.iter().last() else {
+    return;
+  };
+
+  if let Ok((mut text, mut notice)) = notice_query.get_single_mut() {
+    text.sections[0].value = message.clone();
+    notice.0.reset();
+    return;
+  }
+
+  commands
+    .spawn(TextBundle::from_section(
+      message.clone(),
+      TextStyle {
+        font: ass.load(FONT),
+        font_size: 30.0,
+        color: Color::WHITE,
+      }
+    ))
+    .insert(Notice(Timer::from_seconds(NOTICE_TIME, TimerMode::Once)));
+}
+
+fn expire_notice(
+  mut commands: Commands,
+  time: Res<Time>,
+  mut notice_query: Query<(Entity, &mut Notice)>
+) {
+  for (entity, mut notice) in &mut notice_query {
+    if notice.0.tick(time.delta()).finished() {
+      commands.entity(entity).despawn_recursive();
+    }
+  }
+}
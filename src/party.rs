@@ -0,0 +1,179 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+
+use crate::{AppState, combat_stats::{CombatStats, EffectiveStats}, experience::Experience, skills::KnownSkills, status::StatusEffects};
+
+pub const MAX_PARTY_SIZE: usize = 4;
+const BATTLER_X: f32 = 100.0;
+const BATTLER_SPACING: f32 = 56.0;
+
+pub struct PartyPlugin;
+
+/// Everyone who fights on the player's side, in command order.
+#[derive(Resource, Default)]
+pub struct Party {
+  pub members: Vec<Entity>
+}
+
+impl Party {
+    pub fn contains(&self, entity: Entity) -> bool {
+      self.members.contains(&entity)
+    }
+
+    /// Returns `false` when the party is already full.
+    pub fn add(&mut self, member: Entity) -> bool {
+      if self.members.len() >= MAX_PARTY_SIZE {
+        return false;
+      }
+      if !self.contains(member) {
+        self.members.push(member);
+      }
+      true
+    }
+
+    pub fn living_members(&self, stats_query: &Query<&CombatStats>) -> Vec<Entity> {
+      self
+        .members
+        .iter()
+        .copied()
+        .filter(|&member| stats_query.get(member).is_ok_and(|stats| stats.health > 0))
+        .collect()
+    }
+}
+
+/// `order` decides where a member sits in the party; lower goes first.
+#[derive(Component, Inspectable)]
+pub struct PartyMember {
+  pub order: usize
+}
+
+/// Side-view sheet a party member is drawn with in combat.
+#[derive(Component, Clone)]
+pub struct Battler {
+  pub texture: &'static str,
+  pub tile_size: Vec2,
+  pub columns: usize,
+  pub rows: usize,
+  pub index: usize,
+  pub flip_x: bool
+}
+
+/// The sprite standing in for a party member during combat.
+#[derive(Component)]
+pub struct PartyBattler {
+  pub member: Entity
+}
+
+impl Plugin for PartyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Party>()
+        .add_startup_system(spawn_companion)
+        .add_system(register_party_members)
+        .add_system_set(
+          SystemSet::on_enter(AppState::Combat).with_system(spawn_battlers)
+        )
+        .add_system_set(
+          SystemSet::on_update(AppState::Combat).with_system(hide_defeated_battlers)
+        )
+        .add_system_set(
+          SystemSet::on_exit(AppState::Combat).with_system(despawn_battlers)
+        );
+    }
+}
+
+fn spawn_companion(mut commands: Commands) {
+    commands
+        .spawn(Name::new("Ash"))
+        .insert(PartyMember { order: 1 })
+        .insert(Battler {
+            texture: "mystic_woods_free_v0.2/sprites/characters/player.png",
+            tile_size: Vec2::new(48.0, 48.0),
+            columns: 6,
+            rows: 5,
+            index: 6,
+            flip_x: true
+        })
+        .insert(CombatStats {
+            health: 5,
+            max_health: 5,
+            attack: 2,
+            defense: 2,
+            mana: 6,
+            max_mana: 6,
+        })
+        .insert(EffectiveStats::default())
+        .insert(StatusEffects::default())
+        .insert(KnownSkills(vec!["Heal".to_string(), "Regen".to_string(), "Venom".to_string(), "Blizzard".to_string()]))
+        .insert(Experience::default());
+}
+
+fn register_party_members(
+    mut party: ResMut<Party>,
+    member_query: Query<(Entity, &PartyMember, Option<&Name>), Added<PartyMember>>
+) {
+    let mut new_members: Vec<(Entity, &PartyMember, Option<&Name>)> = member_query.iter().collect();
+    new_members.sort_by_key(|(_, member, _)| member.order);
+
+    for (entity, _, name) in new_members {
+        if !party.add(entity) {
+            warn!("Party is full, {:?} was not added", name);
+        }
+    }
+}
+
+fn spawn_battlers(
+    mut commands: Commands,
+    party: Res<Party>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    battler_query: Query<&Battler>
+) {
+    let count = party.members.len() as f32;
+    for (index, &member) in party.members.iter().enumerate() {
+        let Ok(battler) = battler_query.get(member) else {
+            continue;
+        };
+
+        let texture_handle = asset_server.load(battler.texture);
+        let texture_atlas = TextureAtlas::from_grid(texture_handle, battler.tile_size, battler.columns, battler.rows, None, None);
+        let y = ((count - 1.0) / 2.0 - index as f32) * BATTLER_SPACING;
+
+        commands
+            .spawn(SpriteSheetBundle {
+                texture_atlas: texture_atlases.add(texture_atlas),
+                sprite: TextureAtlasSprite {
+                    index: battler.index,
+                    flip_x: battler.flip_x,
+                    ..default()
+                },
+                transform: Transform {
+                    translation: Vec3::new(BATTLER_X, y, 900.0),
+                    ..Default::default()
+                },
+                ..default()
+            })
+            .insert(PartyBattler { member });
+    }
+}
+
+fn hide_defeated_battlers(
+    mut battler_query: Query<(&PartyBattler, &mut Visibility)>,
+    stats_query: Query<&CombatStats>
+) {
+    for (battler, mut visibility) in &mut battler_query {
+        let alive = stats_query.get(battler.member).is_ok_and(|stats| stats.health > 0);
+        if visibility.is_visible != alive {
+            visibility.is_visible = alive;
+        }
+    }
+}
+
+fn despawn_battlers(
+  mut commands: Commands,
+  battler_query: Query<Entity, With<PartyBattler>>
+) {
+  for entity in battler_query.iter() {
+    commands.entity(entity).despawn_recursive();
+  }
+}
@@ -4,35 +4,92 @@ use bevy_inspector_egui::Inspectable;
 use bevy_ecs_ldtk::LdtkEntity;
 use bevy_ecs_ldtk::{prelude::GridCoords};
 
-use crate::combat_stats::CombatStats;
-use crate::enemy::OverWorldEnemy;
+use crate::actions::Action;
+use crate::animation::{AnimationClip, AnimationState, ClipEnd, Facing, SpriteAnimation};
+use crate::combat_stats::{CombatStats, EffectiveStats};
+use crate::equipment::{Equipment, SpareEquipment};
+use crate::experience::Experience;
+use crate::party::{Battler, PartyMember};
+use crate::skills::KnownSkills;
+use crate::status::StatusEffects;
+use crate::items::Inventory;
+use crate::loot::Wallet;
+use crate::enemy::{OverWorldEnemy, EnemySpawnLink, EnemySpawnEntity};
 use crate::{TILE_SIZE, AppState};
 use crate::collider::Collider;
 use crate::combat::EnterCombatEvent;
 pub struct PlayerPlugin;
 
+/// Seconds the player takes to walk one tile.
+const STEP_TIME: f32 = 0.18;
+const PLAYER_FRAME_TIME: f32 = 0.12;
+/// Frames per row in `Character_004.png`.
+const PLAYER_COLUMNS: usize = 3;
+
 #[derive(Component, Inspectable)]
 pub struct Player;
 
+/// Walks an entity from tile to tile. `step_time` is how long one tile takes; a direction
+/// pressed during a step is queued and taken as soon as the step ends.
+#[derive(Component)]
+pub struct GridMovement {
+  pub step_time: f32,
+  /// The last direction walked or tried.
+  pub facing: Vec2,
+  step: Option<Step>,
+  queued: Option<Vec2>
+}
+
+struct Step {
+  from: Vec3,
+  to: Vec3,
+  elapsed: f32
+}
+
+impl GridMovement {
+    pub fn new(step_time: f32) -> Self {
+      GridMovement { step_time, facing: Vec2::NEG_Y, step: None, queued: None }
+    }
+
+    pub fn is_moving(&self) -> bool {
+      self.step.is_some()
+    }
+
+    /// Drops the step in progress, e.g. when the entity is put somewhere else.
+    pub fn stop(&mut self) {
+      self.step = None;
+      self.queued = None;
+    }
+}
+
+/// Where the player lands the next time a level spawns, instead of `Player_spawn`,
+/// e.g. after loading a save made in another level.
+#[derive(Resource, Default)]
+pub struct SpawnOverride(pub Option<GridCoords>);
+
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app
+        .init_resource::<SpawnOverride>()
         .add_startup_system(spawn_player)
         .add_system_set(
-          SystemSet::on_enter(AppState::OverWorld).with_system(show_player).with_system(show_overworld_enemys)
+          SystemSet::on_enter(AppState::OverWorld)
+            .with_system(show_player)
+            .with_system(show_overworld_enemys)
+            .with_system(release_direction_keys)
         )
         .add_system_set(
           SystemSet::on_exit(AppState::OverWorld).with_system(hide_player).with_system(hide_overworld_enemys)
         )
         .add_system_set(
-          SystemSet::on_update(AppState::OverWorld).with_system(move_player_to_spawn).with_system(player_movement)
+          SystemSet::on_update(AppState::OverWorld)
+            .with_system(move_player_to_spawn)
+            .with_system(player_movement)
+            .with_system(animate_player.after(player_movement))
         );
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
-pub struct PlayerSpawn;
-
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Component)]
 pub struct PlayerSpawnEntity;
 
@@ -43,13 +100,38 @@ pub struct PlayerSpawnBundle {
     grid_coords: GridCoords,
 }
 
+/// `Character_004.png` has one row per facing (down, left, right, up); the middle frame of
+/// each row is the standing pose.
+fn player_animation() -> SpriteAnimation {
+    Facing::ALL.into_iter().enumerate().fold(SpriteAnimation::new(PLAYER_FRAME_TIME), |animation, (row, facing)| {
+        let first = row * PLAYER_COLUMNS;
+        animation
+            .with_clip(AnimationState::Idle, Some(facing), AnimationClip {
+                frames: vec![first + 1],
+                end: ClipEnd::Loop
+            })
+            .with_clip(AnimationState::Walk, Some(facing), AnimationClip {
+                frames: vec![first, first + 1, first + 2, first + 1],
+                end: ClipEnd::Loop
+            })
+    })
+}
+
+fn starting_inventory() -> Inventory {
+    let mut inventory = Inventory::default();
+    inventory.add("Potion", 3);
+    inventory.add("Ether", 1);
+    inventory.add("Antidote", 2);
+    inventory
+}
+
 fn spawn_player(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
     let texture_handle = asset_server.load("player/Character_004.png");
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(24.0, 24.0), 3, 4, None, None);
+    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(24.0, 24.0), PLAYER_COLUMNS, 4, None, None);
     let texture_atlas_handle = texture_atlases.add(texture_atlas);
     commands
         .spawn(SpriteSheetBundle {
@@ -63,62 +145,144 @@ fn spawn_player(
         .insert(Name::new("Player"))
         .insert(Player)
         .insert(Collider)
+        .insert(GridMovement::new(STEP_TIME))
+        .insert(player_animation())
         .insert(CombatStats {
             health: 6,
             max_health: 6,
             attack: 3,
             defense: 2,
+            mana: 4,
+            max_mana: 4,
+        })
+        .insert(Experience::default())
+        .insert(EffectiveStats::default())
+        .insert(Equipment {
+            weapon: Some("Bronze Sword".to_string()),
+            ..default()
+        })
+        .insert(SpareEquipment(vec![
+            "Iron Sword".to_string(),
+            "Leather Armor".to_string(),
+            "Chain Mail".to_string(),
+            "Power Ring".to_string(),
+        ]))
+        .insert(StatusEffects::default())
+        .insert(starting_inventory())
+        .insert(Wallet::default())
+        .insert(KnownSkills(vec!["Fire".to_string(), "Thunder".to_string(), "Guard".to_string(), "Focus".to_string(), "Meditate".to_string()]))
+        .insert(PartyMember { order: 0 })
+        .insert(Battler {
+            texture: "player/Character_004_Battler.png",
+            tile_size: Vec2::splat(32.0),
+            columns: 9,
+            rows: 6,
+            index: 0,
+            flip_x: false
         });
 }
 
 fn move_player_to_spawn(
-    mut player_query: Query<&mut Transform, With<Player>>,
+    mut player_query: Query<(&mut Transform, &mut GridMovement), With<Player>>,
+    mut spawn_override: ResMut<SpawnOverride>,
     player_spawn_query: Query<&GridCoords, Added<PlayerSpawnEntity>>,
 ) {
-    player_spawn_query.for_each(|&grid_coords| {
-        let mut player_transform = player_query.single_mut();
+    player_spawn_query.for_each(|&spawn_coords| {
+        let (mut player_transform, mut movement) = player_query.single_mut();
+        let grid_coords = spawn_override.0.take().unwrap_or(spawn_coords);
+        movement.stop();
 
         player_transform.translation.x = grid_coords.x as f32 * TILE_SIZE;
         player_transform.translation.y = grid_coords.y as f32 * TILE_SIZE;
     });
 }
 
+const DIRECTION_ACTIONS: [(Action, Vec2); 4] = [
+    (Action::MoveUp, Vec2::Y),
+    (Action::MoveLeft, Vec2::NEG_X),
+    (Action::MoveRight, Vec2::X),
+    (Action::MoveDown, Vec2::NEG_Y),
+];
+
+/// Makes the player press a direction again after a fight, so a key still held from before
+/// does not walk straight back into an enemy that was fled from.
+fn release_direction_keys(mut actions: ResMut<Input<Action>>) {
+    for (action, _) in DIRECTION_ACTIONS {
+      actions.reset(action);
+    }
+}
+
+/// Walks the player one tile at a time for as long as a direction is held. Walls and
+/// enemies are checked against the destination tile before each step starts.
+#[allow(clippy::type_complexity)]
 fn player_movement(
-    mut player_query: Query<(&mut Transform, &mut Player)>,
-    keyboard: ResMut<Input<KeyCode>>,
+    mut player_query: Query<(&mut Transform, &mut GridMovement), With<Player>>,
+    actions: Res<Input<Action>>,
+    time: Res<Time>,
     wall_query: Query<&Transform, (Without<Player>, With<Collider>)>,
-    overworld_enemy_query: Query<&Transform, (Without<Player>, With<OverWorldEnemy>)>,
+    overworld_enemy_query: Query<(Entity, &Transform, &EnemySpawnLink), (Without<Player>, With<OverWorldEnemy>)>,
+    enemy_spawn_query: Query<&EnemySpawnEntity>,
     mut enter_combat_event: EventWriter<EnterCombatEvent>
 ) {
-    let (mut transform, mut player) = player_query.single_mut();
+    let (mut transform, mut movement) = player_query.single_mut();
 
+    if let Some(direction) = DIRECTION_ACTIONS
+      .iter()
+      .find(|(action, _)| actions.just_pressed(*action))
+      .map(|&(_, direction)| direction) {
+      movement.queued = Some(direction);
+    }
 
-    let mut x_delta = 0.0;
-    let mut y_delta = 0.0;
-    if keyboard.just_pressed(KeyCode::W) {
-      y_delta += TILE_SIZE
-    } else if keyboard.just_pressed(KeyCode::A) {
-      x_delta -= TILE_SIZE
-    } else if keyboard.just_pressed(KeyCode::D) {
-      x_delta += TILE_SIZE
-    } else if keyboard.just_pressed(KeyCode::S) {
-      y_delta -= TILE_SIZE
+    let step_time = movement.step_time;
+    if let Some(step) = &mut movement.step {
+      step.elapsed += time.delta_seconds();
+      let t = (step.elapsed / step_time).min(1.0);
+      transform.translation = step.from.lerp(step.to, t);
+      if t < 1.0 {
+        return;
+      }
+      movement.step = None;
     }
 
-    let target = transform.translation + Vec3::new(x_delta, y_delta, 0.0);
+    let held = DIRECTION_ACTIONS
+      .iter()
+      .find(|(action, _)| actions.pressed(*action))
+      .map(|&(_, direction)| direction);
+    let Some(direction) = movement.queued.take().or(held) else {
+      return;
+    };
+    movement.facing = direction;
 
-    if enemy_collision_check(target, &overworld_enemy_query) {
-      enter_combat_event.send(EnterCombatEvent {  });
+    let target = transform.translation + (direction * TILE_SIZE).extend(0.0);
+
+    if let Some((overworld_enemy, link)) = enemy_collision_check(target, &overworld_enemy_query) {
+      let enemy_spawn = enemy_spawn_query.get(link.spawn).expect("Overworld enemy without a spawn!");
+      enter_combat_event.send(EnterCombatEvent {
+        overworld_enemy,
+        enemy_spawn: enemy_spawn.clone()
+      });
     } else if wall_collision_check(target, &wall_query) {
-      transform.translation = target;
+      movement.step = Some(Step {
+        from: transform.translation,
+        to: target,
+        elapsed: 0.0
+      });
+    }
+}
+
+fn animate_player(mut player_query: Query<(&GridMovement, &mut SpriteAnimation), With<Player>>) {
+    for (movement, mut animation) in &mut player_query {
+      animation.face(Facing::from_direction(movement.facing));
+      animation.play(if movement.is_moving() { AnimationState::Walk } else { AnimationState::Idle });
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn enemy_collision_check(
   target_player_pos: Vec3,
-  overworld_enemy_query: &Query<&Transform, (Without<Player>, With<OverWorldEnemy>)>
-) -> bool {
-  for overworld_transform in overworld_enemy_query.iter() {
+  overworld_enemy_query: &Query<(Entity, &Transform, &EnemySpawnLink), (Without<Player>, With<OverWorldEnemy>)>
+) -> Option<(Entity, EnemySpawnLink)> {
+  for (overworld_enemy, overworld_transform, link) in overworld_enemy_query.iter() {
     let collision = collide(
       target_player_pos,
       Vec2::splat(TILE_SIZE * 0.9),
@@ -127,10 +291,10 @@ fn enemy_collision_check(
     );
 
     if collision.is_some() {
-      return true;
+      return Some((overworld_enemy, *link));
     }
   }
-  false
+  None
 }
 
 fn wall_collision_check(
@@ -174,7 +338,7 @@ fn hide_overworld_enemys(
   children_query: Query<&Children, With<OverWorldEnemy>>,
   mut child_visibility_query: Query<&mut Visibility, Without<OverWorldEnemy>>
 ){
-  for (mut over_world_enemy_vis) in &mut over_world_enemy_query{
+  for mut over_world_enemy_vis in &mut over_world_enemy_query{
     over_world_enemy_vis.is_visible = false;
 
     if let Ok(children) = children_query.get_single() {
@@ -209,7 +373,7 @@ fn show_overworld_enemys(
   children_query: Query<&Children, With<OverWorldEnemy>>,
   mut child_visibility_query: Query<&mut Visibility, Without<OverWorldEnemy>>
 ) {
-  for (mut over_world_enemy_vis) in &mut over_world_enemy_query{
+  for mut over_world_enemy_vis in &mut over_world_enemy_query{
     over_world_enemy_vis.is_visible = true;
 
     if let Ok(children) = children_query.get_single() {
@@ -220,4 +384,65 @@ fn show_overworld_enemys(
       }
     }   
   }  
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use bevy::asset::AssetPlugin;
+    use bevy::core::CorePlugin;
+    use bevy::input::InputPlugin;
+
+    use crate::actions::{ActionBindings, ActionsPlugin, InjectedActions};
+
+    use super::*;
+
+    /// The overworld without a window or level. Time only moves when `advance` says so.
+    fn overworld_app() -> App {
+      let mut app = App::new();
+      app
+        .add_plugin(CorePlugin::default())
+        .add_plugin(AssetPlugin::default())
+        .add_plugin(InputPlugin)
+        .add_asset::<TextureAtlas>()
+        .init_resource::<Time>()
+        .add_state(AppState::OverWorld)
+        .add_event::<EnterCombatEvent>()
+        .add_plugin(ActionsPlugin { bindings: Some(ActionBindings::default()) })
+        .add_plugin(PlayerPlugin);
+      app.update();
+      app
+    }
+
+    fn advance(app: &mut App, seconds: f32) {
+      let mut time = app.world.resource_mut::<Time>();
+      let now = time.last_update().unwrap_or_else(Instant::now) + Duration::from_secs_f32(seconds);
+      time.update_with_instant(now);
+      app.update();
+    }
+
+    fn player(app: &mut App) -> (Vec3, bool, Vec2) {
+      let mut query = app.world.query_filtered::<(&Transform, &GridMovement), With<Player>>();
+      let (transform, movement) = query.single(&app.world);
+      (transform.translation, movement.is_moving(), movement.facing)
+    }
+
+    #[test]
+    fn injected_move_up_walks_one_tile() {
+      let mut app = overworld_app();
+      let (start, _, _) = player(&mut app);
+
+      app.world.resource_mut::<InjectedActions>().0.insert(Action::MoveUp);
+      advance(&mut app, 0.0);
+      let (_, moving, facing) = player(&mut app);
+      assert!(moving);
+      assert_eq!(facing, Vec2::Y);
+
+      app.world.resource_mut::<InjectedActions>().0.remove(&Action::MoveUp);
+      advance(&mut app, STEP_TIME);
+      let (position, moving, _) = player(&mut app);
+      assert!(!moving);
+      assert_eq!(position, start + Vec3::Y * TILE_SIZE);
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+pub struct RngPlugin;
+
+/// Single source of randomness for gameplay. Pass `--seed <n>` on the command line to
+/// replay a run exactly.
+#[derive(Resource)]
+pub struct GameRng {
+  seed: u64,
+  rng: ChaCha8Rng
+}
+
+impl GameRng {
+    pub fn from_seed(seed: u64) -> Self {
+      GameRng {
+        seed,
+        rng: ChaCha8Rng::seed_from_u64(seed)
+      }
+    }
+
+    pub fn seed(&self) -> u64 {
+      self.seed
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+      self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+      self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+      self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+      self.rng.try_fill_bytes(dest)
+    }
+}
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = seed_from_args(std::env::args()).unwrap_or_else(seed_from_time);
+        app
+        .insert_resource(GameRng::from_seed(seed))
+        .add_startup_system(print_seed);
+    }
+}
+
+fn seed_from_args(args: impl Iterator<Item = String>) -> Option<u64> {
+  let mut args = args.skip(1);
+  while let Some(arg) = args.next() {
+    let value = if arg == "--seed" {
+      args.next()
+    } else {
+      arg.strip_prefix("--seed=").map(str::to_string)
+    };
+
+    if let Some(value) = value {
+      match value.parse() {
+        Ok(seed) => return Some(seed),
+        Err(_) => warn!("Ignoring invalid seed {:?}", value),
+      }
+    }
+  }
+  None
+}
+
+fn seed_from_time() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |duration| duration.as_nanos() as u64)
+}
+
+fn print_seed(rng: Res<GameRng>) {
+  info!("RNG seed: {} (rerun with --seed {} to reproduce)", rng.seed(), rng.seed());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed(args: &[&str]) -> Option<u64> {
+      seed_from_args(std::iter::once("chat_rpg").chain(args.iter().copied()).map(str::to_string))
+    }
+
+    #[test]
+    fn reads_seed_as_separate_argument() {
+      assert_eq!(seed(&["--seed", "42"]), Some(42));
+    }
+
+    #[test]
+    fn reads_seed_with_equals() {
+      assert_eq!(seed(&["--seed=42"]), Some(42));
+    }
+
+    #[test]
+    fn ignores_malformed_seed() {
+      assert_eq!(seed(&["--seed", "forty-two"]), None);
+      assert_eq!(seed(&["--seed=-1"]), None);
+      assert_eq!(seed(&["--seed"]), None);
+    }
+
+    #[test]
+    fn keeps_looking_past_a_malformed_seed() {
+      assert_eq!(seed(&["--seed=x", "--seed", "7"]), Some(7));
+    }
+
+    #[test]
+    fn skips_program_name() {
+      assert_eq!(seed_from_args(["--seed=3".to_string()].into_iter()), None);
+    }
+}
@@ -0,0 +1,501 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy_ecs_ldtk::prelude::{GridCoords, LevelSelection};
+use ron::value::{Map, Number, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::{TILE_SIZE, AppState, chest::OpenedChests, enemy::DefeatedEnemies, notice::NoticeEvent, party::Party};
+use crate::actions::Action;
+use crate::combat_stats::CombatStats;
+use crate::equipment::{Equipment, SpareEquipment};
+use crate::experience::Experience;
+use crate::items::Inventory;
+use crate::loot::Wallet;
+use crate::player::{GridMovement, Player, SpawnOverride};
+
+/// Bumped whenever `SaveData` changes shape, together with a new entry in `MIGRATIONS`.
+pub const SAVE_VERSION: u32 = 1;
+pub const SAVE_SLOTS: usize = 3;
+/// Saves live under the platform data dir, e.g. `~/.local/share/chat_rpg/saves` on Linux.
+const SAVE_DIR: &str = "chat_rpg/saves";
+
+pub struct SavePlugin;
+
+/// Everything needed to put the world back the way it was. Party members are stored in
+/// party order.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SaveData {
+  pub version: u32,
+  pub level: usize,
+  /// Player grid position.
+  pub position: (i32, i32),
+  pub party: Vec<MemberSave>,
+  pub inventory: Inventory,
+  pub spare_equipment: SpareEquipment,
+  pub gold: u32,
+  pub defeated_enemies: Vec<String>,
+  pub opened_chests: Vec<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MemberSave {
+  pub name: String,
+  pub stats: CombatStats,
+  pub experience: Option<Experience>,
+  pub equipment: Option<Equipment>
+}
+
+/// Upgrades the fields of a save by one version, or explains why it cannot.
+type Migration = fn(&mut Map) -> Result<(), String>;
+
+/// `MIGRATIONS[n]` turns a version `n` save into version `n + 1`, so loading runs every
+/// entry from the save's version onwards. The length keeps this in step with `SAVE_VERSION`.
+const MIGRATIONS: [Migration; SAVE_VERSION as usize] = [add_world_flags];
+
+#[derive(Debug)]
+pub enum SaveError {
+  NoDataDir,
+  Io(io::Error),
+  /// Not valid RON, e.g. a truncated or hand-edited file.
+  Parse(ron::error::SpannedError),
+  Write(ron::Error),
+  /// Valid RON, but not a save record, or one whose `version` is not a whole number.
+  NotASave,
+  /// Written by a newer build of the game.
+  NewerVersion(u32),
+  Migration { from: u32, reason: String },
+  /// The fields do not match `SaveData`, even after migrating.
+  Invalid(ron::Error)
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+      match self {
+        SaveError::NoDataDir => write!(f, "no data directory on this platform"),
+        SaveError::Io(error) if error.kind() == io::ErrorKind::NotFound => write!(f, "slot is empty"),
+        SaveError::Io(error) => write!(f, "{}", error),
+        SaveError::Parse(error) => write!(f, "save file is damaged: {}", error),
+        SaveError::Write(error) => write!(f, "could not write save: {}", error),
+        SaveError::NotASave => write!(f, "file is not a save"),
+        SaveError::NewerVersion(version) => write!(
+          f,
+          "save version {} is newer than this game supports ({})",
+          version,
+          SAVE_VERSION
+        ),
+        SaveError::Migration { from, reason } => {
+          write!(f, "could not upgrade save from version {}: {}", from, reason)
+        }
+        SaveError::Invalid(error) => write!(f, "save does not match version {}: {}", SAVE_VERSION, error),
+      }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+      SaveError::Io(error)
+    }
+}
+
+pub fn slot_path(slot: usize) -> Result<PathBuf, SaveError> {
+  let data_dir = dirs::data_dir().ok_or(SaveError::NoDataDir)?;
+  Ok(data_dir.join(SAVE_DIR).join(format!("slot_{}.ron", slot)))
+}
+
+pub fn write_save(path: &Path, data: &SaveData) -> Result<(), SaveError> {
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent)?;
+  }
+  fs::write(path, format_save(data)?)?;
+  Ok(())
+}
+
+/// The RON text `write_save` puts on disk; `parse_save` reads it back.
+pub fn format_save(data: &SaveData) -> Result<String, SaveError> {
+  ron::ser::to_string_pretty(data, ron::ser::PrettyConfig::default()).map_err(SaveError::Write)
+}
+
+pub fn read_save(path: &Path) -> Result<SaveData, SaveError> {
+  parse_save(&fs::read_to_string(path)?)
+}
+
+/// Reads a save of any known version, migrating it up to `SAVE_VERSION`. The file is read as
+/// loose RON first so older shapes can be fixed up before they have to match `SaveData`.
+/// Saves from before versioning have no `version` field and count as version 0.
+pub fn parse_save(source: &str) -> Result<SaveData, SaveError> {
+  let Value::Map(mut fields) = ron::from_str(source).map_err(SaveError::Parse)? else {
+    return Err(SaveError::NotASave);
+  };
+
+  let version_key = Value::String("version".to_string());
+  let version = match fields.remove(&version_key) {
+    Some(Value::Number(Number::Integer(version))) => {
+      u32::try_from(version).map_err(|_| SaveError::NotASave)?
+    }
+    Some(_) => return Err(SaveError::NotASave),
+    None => 0,
+  };
+  if version > SAVE_VERSION {
+    return Err(SaveError::NewerVersion(version));
+  }
+
+  migrate(&mut fields, version, &MIGRATIONS)?;
+  fields.insert(version_key, Value::Number(Number::from(SAVE_VERSION as i64)));
+  Value::Map(fields).into_rust().map_err(SaveError::Invalid)
+}
+
+/// Runs `migrations` in order, starting with the one that upgrades a `version` save.
+fn migrate(fields: &mut Map, version: u32, migrations: &[Migration]) -> Result<(), SaveError> {
+  for (from, migration) in migrations.iter().enumerate().skip(version as usize) {
+    let from = from as u32;
+    migration(fields).map_err(|reason| SaveError::Migration { from, reason })?;
+  }
+  Ok(())
+}
+
+/// Version 0 saves predate defeated-enemy and opened-chest flags; start both empty.
+fn add_world_flags(fields: &mut Map) -> Result<(), String> {
+  for key in ["defeated_enemies", "opened_chests"] {
+    let key = Value::String(key.to_string());
+    let flags = fields.remove(&key).unwrap_or(Value::Seq(Vec::new()));
+    fields.insert(key, flags);
+  }
+  Ok(())
+}
+
+/// The slot written most recently, if any slot holds a save.
+pub fn latest_slot() -> Option<usize> {
+  (1..=SAVE_SLOTS)
+    .filter_map(|slot| {
+      let modified = fs::metadata(slot_path(slot).ok()?).ok()?.modified().ok()?;
+      Some((modified, slot))
+    })
+    .max()
+    .map(|(_, slot)| slot)
+}
+
+/// Puts a save that has been read from `slot` over the running world.
+pub struct LoadSave {
+  pub slot: usize,
+  pub data: SaveData
+}
+
+/// The slot `SaveGame` writes to and `LoadGame` reads from, counting from 1.
+#[derive(Resource)]
+pub struct SaveSlot(pub usize);
+
+impl Default for SaveSlot {
+    fn default() -> Self {
+      SaveSlot(1)
+    }
+}
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<SaveSlot>()
+        .add_event::<LoadSave>()
+        .add_system(apply_save)
+        .add_system_set(
+          SystemSet::on_update(AppState::OverWorld)
+            .with_system(cycle_save_slot)
+            .with_system(save_game)
+            .with_system(load_game)
+        );
+    }
+}
+
+/// `NextSaveSlot` switches to the next slot.
+fn cycle_save_slot(
+  actions: Res<Input<Action>>,
+  mut slot: ResMut<SaveSlot>,
+  mut notice_event: EventWriter<NoticeEvent>
+) {
+  if actions.just_pressed(Action::NextSaveSlot) {
+    slot.0 = slot.0 % SAVE_SLOTS + 1;
+    notice_event.send(NoticeEvent(format!("Save slot {}", slot.0)));
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_game(
+  actions: Res<Input<Action>>,
+  slot: Res<SaveSlot>,
+  level_selection: Res<LevelSelection>,
+  party: Res<Party>,
+  member_query: Query<(&Name, &CombatStats, Option<&Experience>, Option<&Equipment>)>,
+  player_query: Query<(&Transform, &Inventory, &SpareEquipment, &Wallet), With<Player>>,
+  defeated: Res<DefeatedEnemies>,
+  opened: Res<OpenedChests>,
+  mut notice_event: EventWriter<NoticeEvent>
+) {
+  if !actions.just_pressed(Action::SaveGame) {
+    return;
+  }
+  let Ok((transform, inventory, spare, wallet)) = player_query.get_single() else {
+    return;
+  };
+
+  let level = match *level_selection {
+    LevelSelection::Index(index) => index,
+    _ => 0,
+  };
+  let grid = (transform.translation.truncate() / TILE_SIZE).round();
+  let party = party
+    .members
+    .iter()
+    .filter_map(|&member| {
+      let (name, stats, experience, equipment) = member_query.get(member).ok()?;
+      Some(MemberSave {
+        name: name.to_string(),
+        stats: *stats,
+        experience: experience.copied(),
+        equipment: equipment.cloned()
+      })
+    })
+    .collect();
+  let mut defeated_enemies: Vec<String> = defeated.0.iter().cloned().collect();
+  defeated_enemies.sort();
+  let mut opened_chests: Vec<String> = opened.0.iter().cloned().collect();
+  opened_chests.sort();
+
+  let data = SaveData {
+    version: SAVE_VERSION,
+    level,
+    position: (grid.x as i32, grid.y as i32),
+    party,
+    inventory: inventory.clone(),
+    spare_equipment: spare.clone(),
+    gold: wallet.gold,
+    defeated_enemies,
+    opened_chests
+  };
+
+  let message = match slot_path(slot.0).and_then(|path| write_save(&path, &data)) {
+    Ok(()) => format!("Saved to slot {}", slot.0),
+    Err(error) => {
+      error!("Could not save to slot {}: {}", slot.0, error);
+      format!("Save failed: {}", error)
+    }
+  };
+  notice_event.send(NoticeEvent(message));
+}
+
+/// `LoadGame` loads the current slot.
+fn load_game(
+  actions: Res<Input<Action>>,
+  slot: Res<SaveSlot>,
+  mut load_event: EventWriter<LoadSave>,
+  mut notice_event: EventWriter<NoticeEvent>
+) {
+  if !actions.just_pressed(Action::LoadGame) {
+    return;
+  }
+
+  match slot_path(slot.0).and_then(|path| read_save(&path)) {
+    Ok(data) => load_event.send(LoadSave { slot: slot.0, data }),
+    Err(error) => {
+      warn!("Could not load slot {}: {}", slot.0, error);
+      notice_event.send(NoticeEvent(format!("Load failed: {}", error)));
+    }
+  }
+}
+
+/// Restores a save over the running world. Party members are matched up by position and
+/// name; anyone who does not match is left as is.
+#[allow(clippy::too_many_arguments)]
+fn apply_save(
+  mut load_events: EventReader<LoadSave>,
+  mut level_selection: ResMut<LevelSelection>,
+  mut spawn_override: ResMut<SpawnOverride>,
+  party: Res<Party>,
+  mut member_query: Query<(&Name, &mut CombatStats, Option<&mut Experience>, Option<&mut Equipment>)>,
+  mut player_query: Query<(&mut Transform, &mut GridMovement, &mut Inventory, &mut SpareEquipment, &mut Wallet), With<Player>>,
+  mut defeated: ResMut<DefeatedEnemies>,
+  mut opened: ResMut<OpenedChests>,
+  mut notice_event: EventWriter<NoticeEvent>
+) {
+  for LoadSave { slot, data } in load_events.iter() {
+    let Ok((mut transform, mut movement, mut inventory, mut spare, mut wallet)) = player_query.get_single_mut() else {
+      return;
+    };
+
+    for (saved, &member) in data.party.iter().zip(&party.members) {
+      let Ok((name, mut stats, experience, equipment)) = member_query.get_mut(member) else {
+        continue;
+      };
+      if name.as_str() != saved.name {
+        warn!("Save slot {} has {} where the party has {}", slot, saved.name, name);
+        continue;
+      }
+      *stats = saved.stats;
+      if let (Some(mut experience), Some(saved)) = (experience, saved.experience) {
+        *experience = saved;
+      }
+      if let (Some(mut equipment), Some(saved)) = (equipment, &saved.equipment) {
+        *equipment = saved.clone();
+      }
+    }
+
+    *inventory = data.inventory.clone();
+    *spare = data.spare_equipment.clone();
+    wallet.gold = data.gold;
+    defeated.0 = data.defeated_enemies.iter().cloned().collect();
+    opened.0 = data.opened_chests.iter().cloned().collect();
+
+    let (x, y) = data.position;
+    movement.stop();
+    transform.translation.x = x as f32 * TILE_SIZE;
+    transform.translation.y = y as f32 * TILE_SIZE;
+    if *level_selection != LevelSelection::Index(data.level) {
+      *level_selection = LevelSelection::Index(data.level);
+      spawn_override.0 = Some(GridCoords::new(x, y));
+    }
+
+    notice_event.send(NoticeEvent(format!("Loaded slot {}", slot)));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn save_data() -> SaveData {
+      let mut inventory = Inventory::default();
+      inventory.add("Potion", 2);
+      SaveData {
+        version: SAVE_VERSION,
+        level: 1,
+        position: (4, -2),
+        party: vec![MemberSave {
+          name: "Player".to_string(),
+          stats: CombatStats {
+            health: 7,
+            max_health: 10,
+            attack: 3,
+            defense: 1,
+            mana: 2,
+            max_mana: 5
+          },
+          experience: Some(Experience { level: 2, xp: 15 }),
+          equipment: Some(Equipment {
+            weapon: Some("Bronze Sword".to_string()),
+            ..default()
+          })
+        }],
+        inventory,
+        spare_equipment: SpareEquipment(vec!["Leather Armor".to_string()]),
+        gold: 30,
+        defeated_enemies: vec!["enemy-iid".to_string()],
+        opened_chests: vec!["chest-iid".to_string()]
+      }
+    }
+
+    #[test]
+    fn round_trips_through_ron() {
+      let data = save_data();
+      assert_eq!(parse_save(&format_save(&data).unwrap()).unwrap(), data);
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+      let dir = std::env::temp_dir().join(format!("chat_rpg_save_test_{}", std::process::id()));
+      let path = dir.join("saves").join("slot_1.ron");
+      let data = save_data();
+
+      write_save(&path, &data).unwrap();
+      let loaded = read_save(&path);
+      fs::remove_dir_all(&dir).unwrap();
+
+      assert_eq!(loaded.unwrap(), data);
+    }
+
+    #[test]
+    fn missing_file_reads_as_an_empty_slot() {
+      let error = read_save(&std::env::temp_dir().join("chat_rpg_no_such_save.ron")).unwrap_err();
+      assert_eq!(error.to_string(), "slot is empty");
+    }
+
+    fn fixture(name: &str) -> Result<SaveData, SaveError> {
+      read_save(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/saves").join(name))
+    }
+
+    #[test]
+    fn reads_version_1_fixture() {
+      let data = fixture("v1.ron").unwrap();
+      assert_eq!(data.version, SAVE_VERSION);
+      assert_eq!(data.party[0].name, "Player");
+      assert_eq!(data.inventory.count("Potion"), 2);
+      assert_eq!(data.opened_chests, vec!["chest-iid".to_string()]);
+    }
+
+    #[test]
+    fn migrates_unversioned_fixture() {
+      let data = fixture("v0.ron").unwrap();
+      assert_eq!(data.version, SAVE_VERSION);
+      assert_eq!(data.gold, 30);
+      assert!(data.defeated_enemies.is_empty());
+      assert!(data.opened_chests.is_empty());
+    }
+
+    #[test]
+    fn rejects_damaged_fixtures() {
+      assert!(matches!(fixture("truncated.ron"), Err(SaveError::Parse(_))));
+      assert!(matches!(fixture("not_a_save.ron"), Err(SaveError::NotASave)));
+      assert!(matches!(fixture("version_99.ron"), Err(SaveError::NewerVersion(99))));
+    }
+
+    #[test]
+    fn rejects_versions_that_are_not_whole_numbers() {
+      assert!(matches!(parse_save("(version: \"1\")"), Err(SaveError::NotASave)));
+      assert!(matches!(parse_save("(version: -1)"), Err(SaveError::NotASave)));
+    }
+
+    /// Appends `step` to the save's `trail` field so the order migrations ran in shows.
+    fn mark(fields: &mut Map, step: &str) -> Result<(), String> {
+      let trail = trail(fields).unwrap_or_default();
+      fields.insert(Value::String("trail".to_string()), Value::String(trail + step));
+      Ok(())
+    }
+
+    fn trail(fields: &mut Map) -> Option<String> {
+      match fields.remove(&Value::String("trail".to_string())) {
+        Some(Value::String(trail)) => Some(trail),
+        _ => None,
+      }
+    }
+
+    const TEST_MIGRATIONS: [Migration; 3] = [
+      |fields| mark(fields, "0"),
+      |fields| mark(fields, "1"),
+      |fields| mark(fields, "2"),
+    ];
+
+    #[test]
+    fn migrations_run_in_order_from_the_save_version() {
+      let mut fields = Map::new();
+      migrate(&mut fields, 0, &TEST_MIGRATIONS).unwrap();
+      assert_eq!(trail(&mut fields).as_deref(), Some("012"));
+
+      let mut fields = Map::new();
+      migrate(&mut fields, 2, &TEST_MIGRATIONS).unwrap();
+      assert_eq!(trail(&mut fields).as_deref(), Some("2"));
+
+      let mut fields = Map::new();
+      migrate(&mut fields, 3, &TEST_MIGRATIONS).unwrap();
+      assert_eq!(trail(&mut fields), None);
+    }
+
+    #[test]
+    fn failed_migration_names_its_version() {
+      let migrations: [Migration; 2] = [|_| Ok(()), |_| Err("no party".to_string())];
+      let error = migrate(&mut Map::new(), 0, &migrations).unwrap_err();
+      assert!(matches!(&error, SaveError::Migration { from: 1, reason } if reason == "no party"));
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{combat::FightEffect, damage::Element, data::load_ron, status::StatusEffect};
+
+const SKILLS_PATH: &str = "data/skills.ron";
+
+pub struct SkillsPlugin;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkillTarget {
+  /// One enemy, picked by the player.
+  Single,
+  /// Every enemy still standing.
+  All,
+  /// Whoever uses the skill.
+  User,
+  /// One party member, picked by the player.
+  Ally
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SkillDefinition {
+  pub name: String,
+  pub cost: isize,
+  pub power: isize,
+  pub target: SkillTarget,
+  pub effect: FightEffect,
+  #[serde(default)]
+  pub element: Element,
+  /// Left on every target the skill hits.
+  #[serde(default)]
+  pub status: Option<StatusEffect>
+}
+
+/// Every skill definition from `assets/data/skills.ron`, by name.
+#[derive(Resource, Default)]
+pub struct SkillBook {
+  skills: HashMap<String, SkillDefinition>
+}
+
+impl SkillBook {
+    pub fn from_definitions(definitions: Vec<SkillDefinition>) -> Self {
+      SkillBook {
+        skills: definitions
+          .into_iter()
+          .map(|skill| (skill.name.clone(), skill))
+          .collect()
+      }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SkillDefinition> {
+      self.skills.get(name)
+    }
+}
+
+/// Names of the skills a combatant can use, in menu order.
+#[derive(Component, Clone, Debug, Default)]
+pub struct KnownSkills(pub Vec<String>);
+
+impl Plugin for SkillsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SkillBook::from_definitions(load_ron(SKILLS_PATH)));
+    }
+}
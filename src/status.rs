@@ -0,0 +1,178 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::AppState;
+use crate::combat::{FightEffect, FightEvent, TurnOrder};
+use crate::damage::Element;
+
+pub struct StatusPlugin;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusKind {
+  /// Loses `potency` health at the start of each turn.
+  Poison,
+  /// Skips its turns while it lasts.
+  Stun,
+  /// Adds `potency` defense against incoming hits.
+  Guard,
+  /// Adds `potency` attack to outgoing hits.
+  Focus,
+  /// Recovers `potency` health at the start of each turn.
+  Regen
+}
+
+impl StatusKind {
+    /// Short tag shown next to the combatant's health.
+    pub fn icon(&self) -> &'static str {
+      match self {
+        StatusKind::Poison => "PSN",
+        StatusKind::Stun => "STN",
+        StatusKind::Guard => "GRD",
+        StatusKind::Focus => "FCS",
+        StatusKind::Regen => "RGN",
+      }
+    }
+}
+
+/// `turns` counts down at the start of each of the holder's turns.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatusEffect {
+  pub kind: StatusKind,
+  pub turns: u32,
+  #[serde(default)]
+  pub potency: isize
+}
+
+/// Every status currently on a combatant; one entry per kind.
+#[derive(Component, Clone, Debug, Default)]
+pub struct StatusEffects(pub Vec<StatusEffect>);
+
+impl StatusEffects {
+    /// Reapplying a status replaces the old one, refreshing its duration.
+    pub fn add(&mut self, effect: StatusEffect) {
+      self.0.retain(|existing| existing.kind != effect.kind);
+      self.0.push(effect);
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+      self.0.iter().any(|effect| effect.kind == kind)
+    }
+
+    /// Returns `false` when there was nothing to remove.
+    pub fn remove(&mut self, kind: StatusKind) -> bool {
+      let had = self.has(kind);
+      self.0.retain(|effect| effect.kind != kind);
+      had
+    }
+
+    pub fn attack_bonus(&self) -> isize {
+      self
+        .0
+        .iter()
+        .filter(|effect| effect.kind == StatusKind::Focus)
+        .map(|effect| effect.potency)
+        .sum()
+    }
+
+    pub fn defense_bonus(&self) -> isize {
+      self
+        .0
+        .iter()
+        .filter(|effect| effect.kind == StatusKind::Guard)
+        .map(|effect| effect.potency)
+        .sum()
+    }
+
+    /// Tags like `PSN2 GRD1`, empty without any status.
+    pub fn icons(&self) -> String {
+      self
+        .0
+        .iter()
+        .map(|effect| format!("{}{}", effect.kind.icon(), effect.turns))
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+
+    fn count_down(&mut self) {
+      for effect in &mut self.0 {
+        effect.turns = effect.turns.saturating_sub(1);
+      }
+      self.0.retain(|effect| effect.turns > 0);
+    }
+}
+
+/// The combatant whose turn start was last handled, so each turn ticks once.
+#[derive(Resource, Default)]
+struct StatusTurn {
+  current: Option<Entity>
+}
+
+impl Plugin for StatusPlugin {
+    fn build(&self, app: &mut App) {
+        app
+          .init_resource::<StatusTurn>()
+          .add_system_set(
+            SystemSet::on_update(AppState::Combat).with_system(tick_status_effects)
+          )
+          .add_system_set(
+            SystemSet::on_exit(AppState::Combat).with_system(clear_status_effects)
+          );
+    }
+}
+
+/// Runs when a new combatant comes up: poison and regen take effect, durations count
+/// down and a stunned combatant hands its turn straight on.
+fn tick_status_effects(
+  mut status_turn: ResMut<StatusTurn>,
+  mut turn_order: ResMut<TurnOrder>,
+  mut effects_query: Query<&mut StatusEffects>,
+  name_query: Query<&Name>,
+  mut fight_event: EventWriter<FightEvent>
+) {
+  let active = turn_order.active();
+  if active == status_turn.current {
+    return;
+  }
+  status_turn.current = active;
+
+  let Some(active) = active else {
+    return;
+  };
+  let Ok(mut effects) = effects_query.get_mut(active) else {
+    return;
+  };
+
+  for effect in &effects.0 {
+    let fight_effect = match effect.kind {
+      StatusKind::Poison => FightEffect::Direct,
+      StatusKind::Regen => FightEffect::Heal,
+      StatusKind::Stun | StatusKind::Guard | StatusKind::Focus => continue,
+    };
+    fight_event.send(FightEvent {
+      attacker: active,
+      target: active,
+      damage_amount: effect.potency,
+      element: Element::Physical,
+      effect: fight_effect,
+      status: None
+    });
+  }
+
+  let stunned = effects.has(StatusKind::Stun);
+  effects.count_down();
+  if stunned {
+    let name = name_query.get(active).map_or("?".to_string(), |name| name.to_string());
+    info!("{} is stunned and loses the turn", name);
+    turn_order.end_turn();
+  }
+}
+
+fn clear_status_effects(
+  mut status_turn: ResMut<StatusTurn>,
+  mut effects_query: Query<&mut StatusEffects>
+) {
+  status_turn.current = None;
+  for mut effects in &mut effects_query {
+    effects.0.clear();
+  }
+}
@@ -1,12 +1,14 @@
 use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+
+use crate::VIEW_HEIGHT;
 
 #[derive(Component)]
 pub struct Camera;
 
 pub fn spawn_camera(mut commands: Commands) {
-    let camera = Camera2dBundle {
-        ..default()
-    };
+    let mut camera = Camera2dBundle::default();
+    camera.projection.scaling_mode = ScalingMode::FixedVertical(VIEW_HEIGHT);
 
     commands.spawn(camera).insert(Camera);
 }
\ No newline at end of file
@@ -0,0 +1,67 @@
+use bevy::{prelude::*, ecs::system::EntityCommands};
+
+pub const FONT_SIZE: f32 = 30.0;
+
+/// How big a menu's buttons and their labels are.
+#[derive(Clone, Copy, Debug)]
+pub struct ButtonLayout {
+  pub width: Val,
+  pub height: Val,
+  pub margin: Val,
+  pub font_size: f32
+}
+
+impl ButtonLayout {
+    /// The buttons of the screens opened from the overworld.
+    pub const MENU: ButtonLayout = ButtonLayout {
+      width: Val::Percent(20.),
+      height: Val::Px(30.),
+      margin: Val::Px(5.),
+      font_size: FONT_SIZE
+    };
+}
+
+pub fn spawn_text<'w, 's, 'a>(
+    parent: &'a mut ChildBuilder<'w, 's, '_>,
+    font: Handle<Font>,
+    text: &str,
+    font_size: f32
+) -> EntityCommands<'w, 's, 'a> {
+    parent.spawn(
+      TextBundle::from_section(
+        text.to_string(),
+        TextStyle {
+          font,
+          font_size,
+          color: Color::WHITE,
+        }
+      )
+    )
+}
+
+/// A grey button with `text` centred on it.
+pub fn spawn_button<'w, 's, 'a>(
+    parent: &'a mut ChildBuilder<'w, 's, '_>,
+    font: Handle<Font>,
+    text: &str,
+    layout: ButtonLayout
+) -> EntityCommands<'w, 's, 'a> {
+    let mut button = parent.spawn(ButtonBundle {
+        style: Style {
+            size: Size {
+                width: layout.width,
+                height: layout.height,
+            },
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            margin: UiRect::all(layout.margin),
+            ..Style::default()
+        },
+        background_color: BackgroundColor(Color::GRAY),
+        ..ButtonBundle::default()
+    });
+    button.with_children(|parent| {
+      spawn_text(parent, font, text, layout.font_size);
+    });
+    button
+}